@@ -3,9 +3,14 @@ use std::fs::create_dir_all;
 
 use cosmwasm_schema::{export_schema, export_schema_with_title, remove_schemas, schema_for};
 
+use hackathon_msig::hooks::HookExecuteMsg;
+use hackathon_msig::msg::{ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg};
 use hackathon_msig::{
-    Cw3ExecuteMsg, Cw3QueryMsg, ProposalListResponse, ProposalResponse, ThresholdResponse,
-    VoteListResponse, VoteResponse, VoterDetail, VoterListResponse, VoterResponse,
+    AllowanceListResponse, AllowanceResponse, ConfigResponse, CustomMsg, ExecutionFailureResponse,
+    ExecutionReceiptResponse, GuardQueryMsg, GuardResponse, HooksResponse, ModuleListResponse,
+    ProposalListResponse, ProposalResponse, ProposalStatusResponse, ProposerListResponse,
+    ThresholdResponse, TreasuryResponse, VoteListResponse, VoteResponse, VoterDetail,
+    VoterListResponse, VoterResponse,
 };
 
 fn main() {
@@ -14,14 +19,41 @@ fn main() {
     create_dir_all(&out_dir).unwrap();
     remove_schemas(&out_dir).unwrap();
 
-    export_schema_with_title(&schema_for!(Cw3ExecuteMsg), &out_dir, "ExecuteMsg");
-    export_schema_with_title(&schema_for!(Cw3QueryMsg), &out_dir, "QueryMsg");
-    export_schema_with_title(&schema_for!(ProposalResponse), &out_dir, "ProposalResponse");
-    export_schema(&schema_for!(ProposalListResponse), &out_dir);
+    export_schema(&schema_for!(InstantiateMsg), &out_dir);
+    export_schema_with_title(&schema_for!(ExecuteMsg<CustomMsg>), &out_dir, "ExecuteMsg");
+    export_schema(&schema_for!(QueryMsg), &out_dir);
+    export_schema(&schema_for!(MigrateMsg), &out_dir);
+    export_schema_with_title(
+        &schema_for!(ProposalResponse<CustomMsg>),
+        &out_dir,
+        "ProposalResponse",
+    );
+    export_schema_with_title(
+        &schema_for!(ProposalListResponse<CustomMsg>),
+        &out_dir,
+        "ProposalListResponse",
+    );
+    export_schema(&schema_for!(ProposalStatusResponse), &out_dir);
     export_schema(&schema_for!(VoteResponse), &out_dir);
     export_schema(&schema_for!(VoteListResponse), &out_dir);
     export_schema(&schema_for!(VoterResponse), &out_dir);
     export_schema(&schema_for!(VoterDetail), &out_dir);
     export_schema(&schema_for!(VoterListResponse), &out_dir);
     export_schema(&schema_for!(ThresholdResponse), &out_dir);
+    export_schema(&schema_for!(ConfigResponse), &out_dir);
+    export_schema(&schema_for!(ProposerListResponse), &out_dir);
+    export_schema(&schema_for!(ModuleListResponse), &out_dir);
+    export_schema(&schema_for!(HooksResponse), &out_dir);
+    export_schema(&schema_for!(ExecutionFailureResponse), &out_dir);
+    export_schema(&schema_for!(ExecutionReceiptResponse), &out_dir);
+    export_schema(&schema_for!(AllowanceResponse), &out_dir);
+    export_schema(&schema_for!(AllowanceListResponse), &out_dir);
+    export_schema(&schema_for!(TreasuryResponse), &out_dir);
+    export_schema(&schema_for!(HookExecuteMsg), &out_dir);
+    export_schema_with_title(
+        &schema_for!(GuardQueryMsg<CustomMsg>),
+        &out_dir,
+        "GuardQueryMsg",
+    );
+    export_schema(&schema_for!(GuardResponse), &out_dir);
 }
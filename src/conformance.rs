@@ -0,0 +1,153 @@
+// Runs a regression scenario of cw3 messages against the contract, checking they parse as cw3
+// messages and that the responses stay what the hand-written scenario expects, and checks
+// that custom messages have the JSON of the chain bindings.
+// See testdata/cw3/README.md for where the fixtures come from.
+
+use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+use cosmwasm_std::{from_slice, Env};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::contract::{execute, instantiate, query};
+use crate::msg::{CustomMsg, Cw3ExecuteMsg, ExecuteMsg, InstantiateMsg, QueryMsg};
+use crate::query::Cw3QueryMsg;
+
+const SCENARIO: &str = include_str!("../testdata/cw3/scenario.json");
+
+/// A single step of a scenario, either an execute or a query
+#[derive(Deserialize)]
+struct Step {
+    /// how many blocks after `mock_env()` the step runs
+    #[serde(default)]
+    height: u64,
+    sender: Option<String>,
+    execute: Option<Value>,
+    /// the expected error of the execute message, if it must fail
+    error: Option<String>,
+    query: Option<Value>,
+    response: Option<Value>,
+}
+
+fn mock_env_height(height_delta: u64) -> Env {
+    let mut env = mock_env();
+    env.block.height += height_delta;
+    env
+}
+
+#[test]
+fn cw3_regression_scenario() {
+    let mut deps = mock_dependencies(&[]);
+    // settings added since the original contract are left out to check they have defaults,
+    // apart from an execution window so proposals can expire with a status cw3 does not know
+    let msg: InstantiateMsg = serde_json::from_value(json!({
+        "voters": [
+            {"addr": "voter0001", "weight": 1},
+            {"addr": "voter0002", "weight": 2},
+            {"addr": "voter0003", "weight": 3}
+        ],
+        "threshold": {"absolute_count": {"weight": 3}},
+        "max_voting_period": {"height": 10},
+        "execution_window": {"height": 5},
+        "proposer": {"member": {}}
+    }))
+    .unwrap();
+    instantiate(deps.as_mut(), mock_env(), mock_info("admin0001", &[]), msg).unwrap();
+
+    let steps: Vec<Step> = serde_json::from_str(SCENARIO).unwrap();
+    for (i, step) in steps.into_iter().enumerate() {
+        let env = mock_env_height(step.height);
+
+        if let Some(execute_msg) = step.execute {
+            // the fixture must be a cw3 message, which we accept with the same meaning
+            let cw3: Cw3ExecuteMsg<CustomMsg> = serde_json::from_value(execute_msg.clone())
+                .unwrap_or_else(|e| panic!("step {}: not a cw3 message: {}", i, e));
+            let raw = serde_json::to_vec(&execute_msg).unwrap();
+            let msg: ExecuteMsg<CustomMsg> = from_slice(&raw).unwrap();
+            assert_eq!(msg, ExecuteMsg::from(cw3), "step {}", i);
+
+            let sender = step.sender.expect("execute steps need a sender");
+            let res = execute(deps.as_mut(), env.clone(), mock_info(&sender, &[]), msg);
+            match (res, step.error) {
+                (Ok(_), None) => {}
+                (Err(err), Some(expected)) => assert_eq!(err.to_string(), expected, "step {}", i),
+                (res, expected) => panic!("step {}: got {:?}, expected {:?}", i, res, expected),
+            }
+        }
+
+        if let Some(query_msg) = step.query {
+            let cw3: Cw3QueryMsg = serde_json::from_value(query_msg.clone())
+                .unwrap_or_else(|e| panic!("step {}: not a cw3 query: {}", i, e));
+            let raw = serde_json::to_vec(&query_msg).unwrap();
+            let msg: QueryMsg = from_slice(&raw).unwrap();
+            assert_eq!(msg, QueryMsg::from(cw3), "step {}", i);
+
+            let res = query(deps.as_ref(), env, msg).unwrap();
+            let res: Value = serde_json::from_slice(res.as_slice()).unwrap();
+            let expected = step.response.expect("query steps need a response");
+            assert_eq!(res, expected, "step {}", i);
+        }
+    }
+}
+
+#[cfg(feature = "token-factory")]
+#[test]
+fn token_factory_msgs_conform() {
+    use crate::token_factory::{DenomUnit, Metadata, TokenMsg};
+    use cosmwasm_std::{CosmosMsg, Uint128};
+
+    const MSGS: &str = include_str!("../testdata/token_factory/msgs.json");
+    let denom = "factory/cosmos2contract/ujuno".to_string();
+    let msgs: Vec<CosmosMsg<CustomMsg>> = vec![
+        TokenMsg::CreateDenom {
+            subdenom: "ujuno".to_string(),
+            metadata: None,
+        }
+        .into(),
+        TokenMsg::CreateDenom {
+            subdenom: "umsig".to_string(),
+            metadata: Some(Metadata {
+                description: Some("The token of the multisig".to_string()),
+                denom_units: vec![
+                    DenomUnit {
+                        denom: "factory/cosmos2contract/umsig".to_string(),
+                        exponent: 0,
+                        aliases: vec![],
+                    },
+                    DenomUnit {
+                        denom: "msig".to_string(),
+                        exponent: 6,
+                        aliases: vec!["multisig".to_string()],
+                    },
+                ],
+                base: Some("factory/cosmos2contract/umsig".to_string()),
+                display: Some("msig".to_string()),
+                name: Some("Multisig".to_string()),
+                symbol: Some("MSIG".to_string()),
+            }),
+        }
+        .into(),
+        TokenMsg::ChangeAdmin {
+            denom: denom.clone(),
+            new_admin_address: "somebody".to_string(),
+        }
+        .into(),
+        TokenMsg::MintTokens {
+            denom: denom.clone(),
+            amount: Uint128::new(1000),
+            mint_to_address: "somebody".to_string(),
+        }
+        .into(),
+        TokenMsg::BurnTokens {
+            denom,
+            amount: Uint128::new(1000),
+            burn_from_address: "cosmos2contract".to_string(),
+        }
+        .into(),
+    ];
+
+    // the messages must serialize to exactly what the chain bindings expect, and back
+    let expected: Value = serde_json::from_str(MSGS).unwrap();
+    assert_eq!(serde_json::to_value(&msgs).unwrap(), expected);
+    let parsed: Vec<CosmosMsg<CustomMsg>> = serde_json::from_value(expected).unwrap();
+    assert_eq!(parsed, msgs);
+}
@@ -1,24 +1,47 @@
 use std::cmp::Ordering;
+use std::collections::BTreeMap;
 
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    to_binary, Binary, BlockInfo, CosmosMsg, Deps, DepsMut, Empty, Env, MessageInfo, Order,
-    Response, StdResult,
+    to_binary, Addr, BankMsg, Binary, BlockInfo, Coin, ContractResult, CosmosMsg, Deps, DepsMut,
+    Empty, Env, MessageInfo, Order, Reply, Response, StdResult, Storage, SubMsg,
+    SubMsgExecutionResponse, Uint128, WasmMsg,
 };
 
-// use cw2::set_contract_version;
 use crate::msg::Vote;
 use crate::query::{
-    ProposalListResponse, ProposalResponse, Status, ThresholdResponse, VoteInfo, VoteListResponse,
-    VoteResponse, VoterDetail, VoterListResponse, VoterResponse,
+    AllowanceInfo, AllowanceListResponse, AllowanceResponse, ConfigResponse,
+    ExecutionFailureResponse, ExecutionReceiptResponse, GuardQueryMsg, GuardResponse,
+    HooksResponse, ModuleListResponse, ProposalListResponse, ProposalResponse,
+    ProposalStatusResponse, ProposerListResponse, Status, ThresholdResponse, TreasuryResponse,
+    VoteInfo, VoteListResponse, VoteResponse, VoterDetail, VoterListResponse, VoterResponse,
 };
-use cw_storage_plus::Bound;
+use cw2::{set_contract_version, CONTRACT};
+use cw_storage_plus::{Bound, Map};
 
+use crate::cw4::{Cw4QueryMsg, MemberListResponse, TotalWeightResponse};
 use crate::error::ContractError;
-use crate::expiration::Expiration;
-use crate::msg::{ExecuteMsg, InstantiateMsg, QueryMsg};
-use crate::state::{consume_next_id, parse_id, Config, Proposal, CONFIG, PROPOSALS, VOTERS};
+use crate::expiration::{Duration, Expiration};
+use crate::hooks::{
+    status_changed_hooks, HookExecuteMsg, ProposalHook, VoteHook, HOOKS, HOOK_REPLY_ID,
+};
+use crate::migrations::{migrate_v0_1, parse_version};
+use crate::msg::{
+    ConfigUpdate, CustomMsg, DepositInfo, ExecuteMsg, Executor, InstantiateMsg, MigrateMsg,
+    Proposer, QueryMsg, Voter,
+};
+use crate::state::{
+    add_total, consume_next_id, outflows, parse_id, sub_total, Allowance, Ballot, Config, Proposal,
+    ALLOWANCES, BALLOTS, COMMITTED, COMMITTING, CONFIG, DISPATCHING, EXECUTION_FAILURES,
+    EXECUTION_RECEIPTS, HELD_DEPOSITS, MODULES, PROPOSALS, PROPOSERS, TOTAL_WEIGHT,
+    TOTAL_WEIGHT_KEY, VOTERS,
+};
+
+// version info for migration info,
+// the minor version is bumped whenever the storage layout changes, see `migrate`
+const CONTRACT_NAME: &str = "crates.io:hackathon-msig";
+const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
@@ -26,12 +49,15 @@ pub fn instantiate(
     _env: Env,
     _info: MessageInfo,
     msg: InstantiateMsg,
-) -> Result<Response, ContractError> {
-    if msg.threshold_weight == 0 {
-        return Err(ContractError::ZeroWeight {});
-    }
-    if msg.voters.is_empty() {
-        return Err(ContractError::NoVoters {});
+) -> Result<Response<CustomMsg>, ContractError> {
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    let group = msg
+        .group
+        .map(|group| deps.api.addr_validate(&group))
+        .transpose()?;
+    if group.is_some() && !msg.voters.is_empty() {
+        return Err(ContractError::GroupMembers {});
     }
 
     let mut total_weight = 0;
@@ -41,60 +67,212 @@ pub fn instantiate(
         }
         total_weight += voter.weight;
     }
-
-    if total_weight < msg.threshold_weight {
-        return Err(ContractError::UnreachableWeight {});
+    if let Some(group) = &group {
+        let res: TotalWeightResponse = deps
+            .querier
+            .query_wasm_smart(group, &Cw4QueryMsg::TotalWeight {})?;
+        total_weight = res.weight;
     }
+    if total_weight == 0 {
+        return Err(ContractError::NoVoters {});
+    }
+    msg.threshold.validate(total_weight)?;
+    validate_execution_period(msg.max_voting_period, msg.execution_delay)?;
+    validate_execution_period(msg.max_voting_period, msg.execution_window)?;
+    if let Some(deposit) = &msg.deposit {
+        deposit.validate()?;
+    }
+    let guard = msg
+        .guard
+        .map(|guard| deps.api.addr_validate(&guard))
+        .transpose()?;
 
     let cfg = Config {
-        threshold_weight: msg.threshold_weight,
+        threshold: msg.threshold,
         total_weight,
         max_voting_period: msg.max_voting_period,
+        execution_delay: msg.execution_delay,
+        execution_window: msg.execution_window,
+        executor: msg.executor,
+        proposer: msg.proposer,
+        deposit: msg.deposit,
+        guard,
+        group,
+        track_failures: msg.track_failures,
+        reject_overcommitted: msg.reject_overcommitted,
     };
     CONFIG.save(deps.storage, &cfg)?;
 
-    // add all voters
+    // add all voters, as of height 0 so proposals created in this block can use them
     for voter in msg.voters.iter() {
         let key = deps.api.addr_validate(&voter.addr)?;
-        VOTERS.save(deps.storage, &key, &voter.weight)?;
+        VOTERS.save(deps.storage, &key, &voter.weight, 0)?;
+    }
+    if cfg.group.is_none() {
+        TOTAL_WEIGHT.save(deps.storage, TOTAL_WEIGHT_KEY, &cfg.total_weight, 0)?;
     }
+    // add all allowed proposers
+    for addr in msg.proposers.iter() {
+        let key = deps.api.addr_validate(addr)?;
+        PROPOSERS.save(deps.storage, &key, &Empty {})?;
+    }
+    validate_proposers(deps.storage, cfg.proposer)?;
     Ok(Response::default())
 }
 
+/// Execution periods follow the voting period and must be measured in the same unit
+fn validate_execution_period(
+    max_voting_period: Duration,
+    period: Option<Duration>,
+) -> Result<(), ContractError> {
+    match (max_voting_period, period) {
+        (_, None)
+        | (Duration::Height(_), Some(Duration::Height(_)))
+        | (Duration::Time(_), Some(Duration::Time(_))) => Ok(()),
+        _ => Err(ContractError::WrongExecutionPeriod {}),
+    }
+}
+
+/// Ensures somebody can still create proposals under an allowlist
+fn validate_proposers(storage: &dyn Storage, proposer: Proposer) -> Result<(), ContractError> {
+    if proposer == (Proposer::Allowlist {})
+        && PROPOSERS
+            .keys(storage, None, None, Order::Ascending)
+            .next()
+            .is_none()
+    {
+        return Err(ContractError::NoProposers {});
+    }
+    Ok(())
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(
+    deps: DepsMut,
+    env: Env,
+    _msg: MigrateMsg,
+) -> Result<Response<CustomMsg>, ContractError> {
+    match CONTRACT.may_load(deps.storage)? {
+        Some(stored) => {
+            if stored.contract != CONTRACT_NAME {
+                return Err(ContractError::WrongContract {
+                    contract: stored.contract,
+                });
+            }
+            let version = parse_version(&stored.version)?;
+            if version > parse_version(CONTRACT_VERSION)? {
+                return Err(ContractError::Downgrade {
+                    stored: stored.version,
+                    current: CONTRACT_VERSION.to_string(),
+                });
+            }
+            // 0.2 is the first version that was stored, and still the current layout
+            if !version.starts_with(&[0, 2]) {
+                return Err(ContractError::UnsupportedVersion {
+                    version: stored.version,
+                });
+            }
+        }
+        // 0.1 contracts did not store their version yet
+        None => migrate_v0_1(deps.storage, &env.contract.address)?,
+    }
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "migrate")
+        .add_attribute("version", CONTRACT_VERSION))
+}
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn execute(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
-    msg: ExecuteMsg,
-) -> Result<Response<Empty>, ContractError> {
+    msg: ExecuteMsg<CustomMsg>,
+) -> Result<Response<CustomMsg>, ContractError> {
     match msg {
         ExecuteMsg::Propose {
             title,
             description,
             msgs,
+            earliest,
             latest,
-        } => execute_propose(deps, env, info, title, description, msgs, latest),
+        } => execute_propose(deps, env, info, title, description, msgs, earliest, latest),
         ExecuteMsg::Vote { proposal_id, vote } => execute_vote(deps, env, info, proposal_id, vote),
+        ExecuteMsg::Retract { proposal_id } => execute_retract(deps, env, info, proposal_id),
         ExecuteMsg::Execute { proposal_id } => execute_execute(deps, env, info, proposal_id),
         ExecuteMsg::Close { proposal_id } => execute_close(deps, env, info, proposal_id),
+        ExecuteMsg::UpdateMembers { add, remove } => {
+            execute_update_members(deps, env, info, add, remove)
+        }
+        ExecuteMsg::UpdateConfig(update) => execute_update_config(deps, env, info, update),
+        ExecuteMsg::UpdateProposers { add, remove } => {
+            execute_update_proposers(deps, env, info, add, remove)
+        }
+        ExecuteMsg::EnableModule { module } => execute_enable_module(deps, env, info, module),
+        ExecuteMsg::DisableModule { module } => execute_disable_module(deps, env, info, module),
+        ExecuteMsg::ExecuteFromModule { msgs } => {
+            execute_from_module(deps.as_ref(), env, info, msgs)
+        }
+        ExecuteMsg::DispatchProposal { proposal_id } => {
+            execute_dispatch_proposal(deps, env, info, proposal_id)
+        }
+        ExecuteMsg::AddHook { addr } => execute_add_hook(deps, env, info, addr),
+        ExecuteMsg::RemoveHook { addr } => execute_remove_hook(deps, env, info, addr),
+        ExecuteMsg::SetAllowance {
+            spender,
+            amount,
+            reset_period,
+        } => execute_set_allowance(deps, env, info, spender, amount, reset_period),
+        ExecuteMsg::RemoveAllowance { spender, denom } => {
+            execute_remove_allowance(deps, env, info, spender, denom)
+        }
+        ExecuteMsg::SpendAllowance { to, amount } => {
+            execute_spend_allowance(deps, env, info, to, amount)
+        }
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn execute_propose(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
     title: String,
     description: String,
-    msgs: Vec<CosmosMsg>,
-    // we ignore earliest
+    msgs: Vec<CosmosMsg<CustomMsg>>,
+    earliest: Option<Expiration>,
     latest: Option<Expiration>,
-) -> Result<Response<Empty>, ContractError> {
-    // anyone can create a proposal
-    let vote_power = VOTERS.may_load(deps.storage, &info.sender)?.unwrap_or(0);
-
+) -> Result<Response<CustomMsg>, ContractError> {
     let cfg = CONFIG.load(deps.storage)?;
+    let vote_power = cfg
+        .voter_weight(deps.as_ref(), &info.sender, Some(env.block.height))?
+        .unwrap_or(0);
+
+    // the proposer policy decides who can create a proposal
+    let allowed = match cfg.proposer {
+        Proposer::Anyone {} => true,
+        Proposer::Member {} => vote_power > 0,
+        Proposer::Allowlist {} => PROPOSERS.has(deps.storage, &info.sender),
+    };
+    if !allowed {
+        return Err(ContractError::Unauthorized {});
+    }
+    // a configured deposit must be paid exactly, it is held until the proposal is completed
+    // without one, funds are refused as there would be no way to get them back
+    match &cfg.deposit {
+        Some(deposit) => {
+            deposit.check_funds(&info.funds)?;
+            add_total(deps.storage, &HELD_DEPOSITS, &deposit.amount)?;
+        }
+        None if !info.funds.is_empty() => return Err(ContractError::UnexpectedFunds {}),
+        None => {}
+    }
+    // optionally, proposals can only send what other proposals have not committed yet,
+    // which excludes the deposit that was just paid
+    if cfg.reject_overcommitted {
+        check_uncommitted(deps.as_ref(), &env, &msgs)?;
+    }
 
     // max expires also used as default
     let max_expires = cfg.max_voting_period.after(&env.block);
@@ -106,32 +284,70 @@ pub fn execute_propose(
         return Err(ContractError::WrongExpiration {});
     }
 
-    let status = if vote_power < cfg.threshold_weight {
-        Status::Open
-    } else {
-        Status::Passed
-    };
-    // if info.sender is actually a voter they will never have a vote power of zero (enforced)
-    let voters = if vote_power > 0 {
-        vec![info.sender.clone()]
+    // voting starts right away unless a later start is requested
+    let earliest = earliest.filter(|start| !start.is_expired(&env.block));
+    if let Some(start) = earliest {
+        if start.partial_cmp(&expires) != Some(Ordering::Less) {
+            return Err(ContractError::WrongExpiration {});
+        }
+    }
+    let status = if earliest.is_some() {
+        Status::Pending
     } else {
-        vec![]
+        Status::Open
     };
 
-    // create a proposal
-    let prop = Proposal {
+    // create a proposal, voting is based on the voter set at the start of this block
+    let total_weight = cfg.total_weight_at(deps.as_ref(), env.block.height)?;
+    let mut prop = Proposal {
+        proposer: info.sender.clone(),
         title,
         description,
+        earliest,
         expires,
         msgs,
+        start_height: env.block.height,
         status,
-        voters,
         yes_weight: vote_power,
+        no_weight: 0,
+        abstain_weight: 0,
+        veto_weight: 0,
+        threshold: cfg.threshold,
+        total_weight,
+        execution_delay: cfg.execution_delay,
+        execution_window: cfg.execution_window,
+        passed_at: None,
+        deposit: cfg.deposit,
     };
+    if prop.status == Status::Open && prop.is_passed(&env.block) {
+        prop.set_passed(&env.block);
+    }
     let id = consume_next_id(deps.storage)?;
     PROPOSALS.save(deps.storage, id.into(), &prop)?;
+    // what it sends stays committed until it is executed, rejected or closed
+    for coin in outflows(&prop.msgs) {
+        add_total(deps.storage, &COMMITTED, coin)?;
+    }
+
+    // notify hook contracts of the new proposal and whether it passed right away
+    let mut hooks = HookExecuteMsg::ProposalHook(ProposalHook::NewProposal {
+        id,
+        proposer: info.sender.to_string(),
+    })
+    .into_submsgs(deps.storage)?;
+    hooks.extend(status_changed_hooks(deps.storage, id, status, prop.status)?);
+
+    // if info.sender is actually a voter they will never have a vote power of zero (enforced)
+    if vote_power > 0 {
+        let ballot = Ballot {
+            weight: vote_power,
+            vote: Vote::Yes,
+        };
+        BALLOTS.save(deps.storage, (id.into(), &info.sender), &ballot)?;
+    }
 
     Ok(Response::new()
+        .add_submessages(hooks)
         .add_attribute("action", "propose")
         .add_attribute("sender", info.sender)
         .add_attribute("proposal_id", id.to_string())
@@ -144,777 +360,4139 @@ pub fn execute_vote(
     info: MessageInfo,
     proposal_id: u64,
     vote: Vote,
-) -> Result<Response<Empty>, ContractError> {
-    // only members of the multisig can vote
-    let vote_power = VOTERS
-        .may_load(deps.storage, &info.sender)?
+) -> Result<Response<CustomMsg>, ContractError> {
+    // ensure proposal exists and can be voted on
+    let (mut prop, old_status) = load_open_proposal(deps.storage, &env.block, proposal_id)?;
+
+    // only members of the multisig when the proposal was created can vote
+    let cfg = CONFIG.load(deps.storage)?;
+    let vote_power = cfg
+        .voter_weight(deps.as_ref(), &info.sender, Some(prop.start_height))?
         .ok_or(ContractError::Unauthorized {})?;
 
-    // ensure proposal exists and can be voted on
-    let mut prop = PROPOSALS.load(deps.storage, proposal_id.into())?;
-    if prop.status != Status::Open {
-        return Err(ContractError::NotOpen {});
-    }
-    if prop.expires.is_expired(&env.block) {
-        return Err(ContractError::Expired {});
+    // voters can change their vote while the proposal is open, but not cast the same vote twice
+    let previous = BALLOTS.may_load(deps.storage, (proposal_id.into(), &info.sender))?;
+    if let Some(ballot) = &previous {
+        if ballot.vote == vote {
+            return Err(ContractError::AlreadyVoted {});
+        }
+        // remove the previous vote from the tally
+        prop.remove_vote(ballot.vote, ballot.weight);
     }
+    let ballot = Ballot {
+        weight: vote_power,
+        vote,
+    };
+    BALLOTS.save(deps.storage, (proposal_id.into(), &info.sender), &ballot)?;
 
-    // cast vote if no vote previously cast
-    // voters cannot change their votes from "yes" to "no" for now
-    if prop.voters.contains(&info.sender) {
-        return Err(ContractError::AlreadyVoted {});
+    // update tally and status when the passing or the deciding "no" vote comes in
+    prop.add_vote(vote, vote_power);
+    if prop.is_passed(&env.block) {
+        prop.set_passed(&env.block);
+    } else if prop.is_unreachable() {
+        prop.status = Status::Rejected;
     }
+    PROPOSALS.save(deps.storage, proposal_id.into(), &prop)?;
+    update_committed(deps.storage, &prop, old_status)?;
 
-    // if yes vote, update tally
-    let cfg = CONFIG.load(deps.storage)?;
-    if vote == Vote::Yes {
-        // only store "yes" voters that actually contribute to the vote weight
-        // "no" voters can still change their mind
-        prop.voters.push(info.sender.clone());
-        prop.yes_weight += vote_power;
-        // update status when the passing vote comes in
-        if prop.yes_weight >= cfg.threshold_weight {
-            prop.status = Status::Passed;
-        }
-        PROPOSALS.save(deps.storage, proposal_id.into(), &prop)?;
-    }
+    let mut hooks = HookExecuteMsg::VoteHook(VoteHook::NewVote {
+        proposal_id,
+        voter: info.sender.to_string(),
+        vote,
+    })
+    .into_submsgs(deps.storage)?;
+    hooks.extend(status_changed_hooks(
+        deps.storage,
+        proposal_id,
+        old_status,
+        prop.status,
+    )?);
 
+    let action = if previous.is_some() {
+        "change_vote"
+    } else {
+        "vote"
+    };
     Ok(Response::new()
-        .add_attribute("action", "vote")
+        .add_submessages(hooks)
+        .add_attribute("action", action)
         .add_attribute("sender", info.sender)
         .add_attribute("proposal_id", proposal_id.to_string())
         .add_attribute("status", format!("{:?}", prop.status)))
 }
 
-pub fn execute_execute(
+pub fn execute_retract(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
     proposal_id: u64,
-) -> Result<Response, ContractError> {
-    // anyone can trigger this if the vote passed
+) -> Result<Response<CustomMsg>, ContractError> {
+    // ensure proposal exists and votes can still be retracted
+    let (mut prop, old_status) = load_open_proposal(deps.storage, &env.block, proposal_id)?;
+
+    let ballot = BALLOTS
+        .may_load(deps.storage, (proposal_id.into(), &info.sender))?
+        .ok_or(ContractError::NotVoted {})?;
+    prop.remove_vote(ballot.vote, ballot.weight);
+    PROPOSALS.save(deps.storage, proposal_id.into(), &prop)?;
+    BALLOTS.remove(deps.storage, (proposal_id.into(), &info.sender));
+
+    let mut hooks = HookExecuteMsg::VoteHook(VoteHook::RetractedVote {
+        proposal_id,
+        voter: info.sender.to_string(),
+    })
+    .into_submsgs(deps.storage)?;
+    hooks.extend(status_changed_hooks(
+        deps.storage,
+        proposal_id,
+        old_status,
+        prop.status,
+    )?);
+
+    Ok(Response::new()
+        .add_submessages(hooks)
+        .add_attribute("action", "retract")
+        .add_attribute("sender", info.sender)
+        .add_attribute("proposal_id", proposal_id.to_string())
+        .add_attribute("status", format!("{:?}", prop.status)))
+}
+
+/// Loads a proposal that is currently open for voting, together with its stored status,
+/// marking pending proposals as open once their voting period has started
+fn load_open_proposal(
+    storage: &dyn Storage,
+    block: &BlockInfo,
+    proposal_id: u64,
+) -> Result<(Proposal<CustomMsg>, Status), ContractError> {
+    let mut prop = PROPOSALS.load(storage, proposal_id.into())?;
+    let stored_status = prop.status;
+    if prop.status == Status::Pending && !prop.has_started(block) {
+        return Err(ContractError::NotStarted {});
+    }
+    if ![Status::Pending, Status::Open].contains(&prop.status) {
+        return Err(ContractError::NotOpen {});
+    }
+    if prop.expires.is_expired(block) {
+        return Err(ContractError::Expired {});
+    }
+    // pending proposals with enough weight from the proposer's ballot pass once voting starts
+    if prop.current_status(block) != Status::Open {
+        return Err(ContractError::NotOpen {});
+    }
+    prop.status = Status::Open;
+    Ok((prop, stored_status))
+}
 
+pub fn execute_execute(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    proposal_id: u64,
+) -> Result<Response<CustomMsg>, ContractError> {
     let mut prop = PROPOSALS.load(deps.storage, proposal_id.into())?;
+
+    // the executor policy decides who can trigger this if the vote passed
+    let cfg = CONFIG.load(deps.storage)?;
+    let allowed = match cfg.executor {
+        Executor::Anyone {} => true,
+        Executor::Member {} => cfg
+            .voter_weight(deps.as_ref(), &info.sender, None)?
+            .is_some(),
+        Executor::Proposer {} => info.sender == prop.proposer,
+    };
+    if !allowed {
+        return Err(ContractError::UnauthorizedExecutor {});
+    }
+
     // we allow execution even after the proposal "expiration" as long as all vote come in before
-    // that point. If it was approved on time, it can be executed any time.
-    if prop.status != Status::Passed {
-        return Err(ContractError::WrongExecuteStatus {});
+    // that point. If it was approved on time, it can be executed until its execution window ends.
+    match prop.current_status(&env.block) {
+        // failed executions can be retried
+        Status::Passed | Status::ExecutionFailed => {}
+        Status::ExecutionExpired => return Err(ContractError::ExecutionExpired {}),
+        _ => return Err(ContractError::WrongExecuteStatus {}),
+    }
+    if let Some(timelock) = prop.timelock()? {
+        if !timelock.is_expired(&env.block) {
+            return Err(ContractError::Timelocked {});
+        }
+    }
+    check_guard(deps.as_ref(), &cfg, &info.sender, &prop.msgs)?;
+
+    if cfg.track_failures {
+        // the messages are dispatched by calling ourselves, so that they still execute
+        // atomically, but a failure is reported back to `reply` instead of reverting
+        DISPATCHING.save(deps.storage, &proposal_id)?;
+        let dispatch = WasmMsg::Execute {
+            contract_addr: env.contract.address.to_string(),
+            msg: to_binary(&ExecuteMsg::<CustomMsg>::DispatchProposal { proposal_id })?,
+            funds: vec![],
+        };
+        return Ok(Response::new()
+            .add_submessage(SubMsg::reply_on_error(dispatch, proposal_id))
+            .add_attribute("action", "execute")
+            .add_attribute("sender", info.sender)
+            .add_attribute("proposal_id", proposal_id.to_string()));
     }
 
-    // set it to executed
+    // set it to executed, which returns the deposit to the proposer
+    let old_status = prop.status;
     prop.status = Status::Executed;
+    let refund =
+        take_deposit(deps.storage, &mut prop)?.map(|deposit| refund_deposit(&prop, deposit));
     PROPOSALS.save(deps.storage, proposal_id.into(), &prop)?;
+    update_committed(deps.storage, &prop, old_status)?;
+    let hooks = status_changed_hooks(deps.storage, proposal_id, old_status, prop.status)?;
 
     // dispatch all proposed messages
     Ok(Response::new()
-        // sending the messages is as easy as adding them here to the result type
-        .add_messages(prop.msgs)
+        .add_submessages(receipt_submsgs(proposal_id, prop.msgs))
+        .add_messages(refund)
+        .add_submessages(hooks)
         .add_attribute("action", "execute")
         .add_attribute("sender", info.sender)
         .add_attribute("proposal_id", proposal_id.to_string()))
 }
 
-pub fn execute_close(
+pub fn execute_dispatch_proposal(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
     proposal_id: u64,
-) -> Result<Response<Empty>, ContractError> {
-    // anyone can trigger this if the vote passed
-
-    let mut prop = PROPOSALS.load(deps.storage, proposal_id.into())?;
-    if [Status::Executed, Status::Rejected, Status::Passed]
-        .iter()
-        .any(|x| *x == prop.status)
+) -> Result<Response<CustomMsg>, ContractError> {
+    // only the multisig itself can dispatch, and only for the proposal it is executing
+    if info.sender != env.contract.address
+        || DISPATCHING.may_load(deps.storage)? != Some(proposal_id)
     {
-        return Err(ContractError::WrongCloseStatus {});
-    }
-    if !prop.expires.is_expired(&env.block) {
-        return Err(ContractError::NotExpired {});
+        return Err(ContractError::Unauthorized {});
     }
+    DISPATCHING.remove(deps.storage);
 
-    // set it to failed
-    prop.status = Status::Rejected;
+    // the proposal is only executed, returning the deposit and notifying the hooks,
+    // if all messages go through, as a failure reverts this whole call
+    let mut prop = PROPOSALS.load(deps.storage, proposal_id.into())?;
+    let old_status = prop.status;
+    prop.status = Status::Executed;
+    let refund =
+        take_deposit(deps.storage, &mut prop)?.map(|deposit| refund_deposit(&prop, deposit));
     PROPOSALS.save(deps.storage, proposal_id.into(), &prop)?;
+    update_committed(deps.storage, &prop, old_status)?;
+    EXECUTION_FAILURES.remove(deps.storage, proposal_id.into());
+    let hooks = status_changed_hooks(deps.storage, proposal_id, old_status, prop.status)?;
 
     Ok(Response::new()
-        .add_attribute("action", "close")
-        .add_attribute("sender", info.sender)
+        .add_submessages(receipt_submsgs(proposal_id, prop.msgs))
+        .add_messages(refund)
+        .add_submessages(hooks)
+        .add_attribute("action", "dispatch_proposal")
         .add_attribute("proposal_id", proposal_id.to_string()))
 }
 
-#[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
-    match msg {
-        QueryMsg::Threshold {} => to_binary(&query_threshold(deps)?),
-        QueryMsg::Proposal { proposal_id } => to_binary(&query_proposal(deps, env, proposal_id)?),
-        QueryMsg::Vote { proposal_id, voter } => to_binary(&query_vote(deps, proposal_id, voter)?),
-        QueryMsg::ListProposals { start_after, limit } => {
-            to_binary(&list_proposals(deps, env, start_after, limit)?)
-        }
-        QueryMsg::ReverseProposals {
-            start_before,
-            limit,
-        } => to_binary(&reverse_proposals(deps, env, start_before, limit)?),
-        QueryMsg::ListVotes {
-            proposal_id,
-            start_after,
-            limit,
-        } => to_binary(&list_votes(deps, proposal_id, start_after, limit)?),
-        QueryMsg::Voter { address } => to_binary(&query_voter(deps, address)?),
-        QueryMsg::ListVoters { start_after, limit } => {
-            to_binary(&list_voters(deps, start_after, limit)?)
+/// Dispatches the proposal messages so that `reply` can store a receipt for each of them
+fn receipt_submsgs(proposal_id: u64, msgs: Vec<CosmosMsg<CustomMsg>>) -> Vec<SubMsg<CustomMsg>> {
+    msgs.into_iter()
+        .map(|msg| SubMsg::reply_on_success(msg, proposal_id))
+        .collect()
+}
+
+/// Ensures the multisig holds enough uncommitted funds for what `msgs` send,
+/// i.e. funds that are neither committed to proposals nor held as deposits
+fn check_uncommitted(
+    deps: Deps,
+    env: &Env,
+    msgs: &[CosmosMsg<CustomMsg>],
+) -> Result<(), ContractError> {
+    for (denom, amount) in sum_outflows(msgs)? {
+        let balance = deps.querier.query_balance(&env.contract.address, &denom)?;
+        let committed = COMMITTED.may_load(deps.storage, &denom)?;
+        let deposits = HELD_DEPOSITS.may_load(deps.storage, &denom)?;
+        let uncommitted = balance
+            .amount
+            .saturating_sub(committed.unwrap_or_default())
+            .saturating_sub(deposits.unwrap_or_default());
+        if amount > uncommitted {
+            return Err(ContractError::InsufficientFunds { denom });
         }
     }
+    Ok(())
 }
 
-fn query_threshold(deps: Deps) -> StdResult<ThresholdResponse> {
-    let cfg = CONFIG.load(deps.storage)?;
-    Ok(ThresholdResponse::AbsoluteCount {
-        weight: cfg.threshold_weight,
-        total_weight: cfg.total_weight,
-    })
+/// returns what `msgs` send from the multisig, per denom
+fn sum_outflows(msgs: &[CosmosMsg<CustomMsg>]) -> StdResult<BTreeMap<String, Uint128>> {
+    let mut sums = BTreeMap::new();
+    for coin in outflows(msgs) {
+        let sum: &mut Uint128 = sums.entry(coin.denom.clone()).or_default();
+        *sum = sum.checked_add(coin.amount)?;
+    }
+    Ok(sums)
 }
 
-fn query_proposal(deps: Deps, env: Env, id: u64) -> StdResult<ProposalResponse> {
-    let prop = PROPOSALS.load(deps.storage, id.into())?;
-    let cfg = CONFIG.load(deps.storage)?;
-    let threshold = ThresholdResponse::AbsoluteCount {
-        weight: cfg.threshold_weight,
-        total_weight: cfg.total_weight,
+/// Commits or releases what `prop` sends when its stored status changes from `old_status`
+fn update_committed(
+    storage: &mut dyn Storage,
+    prop: &Proposal<CustomMsg>,
+    old_status: Status,
+) -> StdResult<()> {
+    let update = match (
+        COMMITTING.contains(&old_status),
+        COMMITTING.contains(&prop.status),
+    ) {
+        (false, true) => add_total,
+        (true, false) => sub_total,
+        _ => return Ok(()),
     };
-
-    let status = prop.current_status(&env.block, &cfg.threshold_weight);
-
-    Ok(ProposalResponse {
-        id,
-        title: prop.title,
-        description: prop.description,
-        msgs: prop.msgs,
-        status,
-        expires: prop.expires,
-        threshold,
-    })
+    for coin in outflows(&prop.msgs) {
+        update(storage, &COMMITTED, coin)?;
+    }
+    Ok(())
 }
 
-// settings for pagination
-const MAX_LIMIT: u32 = 30;
-const DEFAULT_LIMIT: u32 = 10;
+/// Takes the deposit of `prop` to settle it, so it is no longer held
+fn take_deposit(
+    storage: &mut dyn Storage,
+    prop: &mut Proposal<CustomMsg>,
+) -> StdResult<Option<DepositInfo>> {
+    let deposit = prop.deposit.take();
+    if let Some(deposit) = &deposit {
+        sub_total(storage, &HELD_DEPOSITS, &deposit.amount)?;
+    }
+    Ok(deposit)
+}
 
-fn list_proposals(
+/// Asks the configured guard contract, if any, to approve `msgs` before they are dispatched
+fn check_guard(
     deps: Deps,
-    env: Env,
-    start_after: Option<u64>,
-    limit: Option<u32>,
-) -> StdResult<ProposalListResponse> {
-    let cfg = CONFIG.load(deps.storage)?;
-    let threshold = ThresholdResponse::AbsoluteCount {
-        weight: cfg.threshold_weight,
-        total_weight: cfg.total_weight,
+    cfg: &Config,
+    sender: &Addr,
+    msgs: &[CosmosMsg<CustomMsg>],
+) -> Result<(), ContractError> {
+    let guard = match &cfg.guard {
+        Some(guard) => guard,
+        None => return Ok(()),
     };
-
-    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
-    let start = start_after.map(Bound::exclusive_int);
-    let props: StdResult<Vec<_>> = PROPOSALS
-        .range(deps.storage, start, None, Order::Ascending)
-        .take(limit)
-        .map(|p| map_proposal(&env.block, &threshold, p))
-        .collect();
-
-    Ok(ProposalListResponse { proposals: props? })
+    let check = GuardQueryMsg::CheckMsgs {
+        sender: sender.to_string(),
+        msgs: msgs.to_vec(),
+    };
+    let res: GuardResponse = deps.querier.query_wasm_smart(guard, &check)?;
+    if !res.allowed {
+        return Err(ContractError::GuardRejected {});
+    }
+    Ok(())
 }
 
-fn reverse_proposals(
-    deps: Deps,
+pub fn execute_close(
+    deps: DepsMut,
     env: Env,
-    start_before: Option<u64>,
-    limit: Option<u32>,
-) -> StdResult<ProposalListResponse> {
-    let cfg = CONFIG.load(deps.storage)?;
-    let threshold = ThresholdResponse::AbsoluteCount {
-        weight: cfg.threshold_weight,
-        total_weight: cfg.total_weight,
-    };
+    info: MessageInfo,
+    proposal_id: u64,
+) -> Result<Response<CustomMsg>, ContractError> {
+    // anyone can trigger this if the vote passed
 
-    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
-    let end = start_before.map(Bound::exclusive_int);
-    let props: StdResult<Vec<_>> = PROPOSALS
-        .range(deps.storage, None, end, Order::Descending)
-        .take(limit)
-        .map(|p| map_proposal(&env.block, &threshold, p))
-        .collect();
+    let mut prop = PROPOSALS.load(deps.storage, proposal_id.into())?;
+    // proposals rejected during voting still need to be closed to settle their deposit
+    let settled = prop.status == Status::Rejected && prop.deposit.is_none();
+    if [Status::Executed, Status::ExecutionExpired].contains(&prop.status) || settled {
+        return Err(ContractError::WrongCloseStatus {});
+    }
+    let status = prop.current_status(&env.block);
+    let refund = match status {
+        // the deposit of a failed proposal is only returned if configured so
+        Status::Rejected => {
+            take_deposit(deps.storage, &mut prop)?.filter(|deposit| deposit.refund_failed)
+        }
+        // proposals that passed but were never executed in time get it back
+        Status::ExecutionExpired => take_deposit(deps.storage, &mut prop)?,
+        // passed proposals can still be executed, quorum proposals can pass once voting is over
+        Status::Passed | Status::ExecutionFailed => return Err(ContractError::WrongCloseStatus {}),
+        _ => return Err(ContractError::NotExpired {}),
+    }
+    .map(|deposit| refund_deposit(&prop, deposit));
 
-    Ok(ProposalListResponse { proposals: props? })
-}
+    let old_status = prop.status;
+    prop.status = status;
+    PROPOSALS.save(deps.storage, proposal_id.into(), &prop)?;
+    update_committed(deps.storage, &prop, old_status)?;
+    let hooks = status_changed_hooks(deps.storage, proposal_id, old_status, prop.status)?;
 
-fn map_proposal(
-    block: &BlockInfo,
-    threshold: &ThresholdResponse,
-    item: StdResult<(Vec<u8>, Proposal)>,
-) -> StdResult<ProposalResponse> {
-    let (key, prop) = item?;
-    let status = prop.current_status(
-        block,
-        match threshold {
-            ThresholdResponse::AbsoluteCount { weight, .. } => weight,
-        },
-    );
-    Ok(ProposalResponse {
-        id: parse_id(&key)?,
-        title: prop.title,
-        description: prop.description,
-        msgs: prop.msgs,
-        status,
-        expires: prop.expires,
-        threshold: threshold.clone(),
-    })
+    Ok(Response::new()
+        .add_messages(refund)
+        .add_submessages(hooks)
+        .add_attribute("action", "close")
+        .add_attribute("sender", info.sender)
+        .add_attribute("proposal_id", proposal_id.to_string()))
 }
 
-// as we only store yes votes instead of all votes, this misses the "no"/"abstain" voters
-fn query_vote(deps: Deps, proposal_id: u64, voter: String) -> StdResult<VoteResponse> {
-    let voter = deps.api.addr_validate(&voter)?;
-    let prop = PROPOSALS.load(deps.storage, proposal_id.into())?;
-
-    let vote = prop
-        .voters
-        .iter()
-        .find(|&addr| addr == &voter)
-        .map(|addr| VoteInfo {
-            voter: addr.into(),
-            vote: Vote::Yes,
-            // could use the weight of the voter but that does not necessarily represent the weight at time of voting anymore
-            weight: 0,
-        });
-    Ok(VoteResponse { vote })
+fn refund_deposit(prop: &Proposal<CustomMsg>, deposit: DepositInfo) -> BankMsg {
+    BankMsg::Send {
+        to_address: prop.proposer.to_string(),
+        amount: vec![deposit.amount],
+    }
 }
 
-fn list_votes(
-    deps: Deps,
-    proposal_id: u64,
-    start_after: Option<String>,
-    limit: Option<u32>,
-) -> StdResult<VoteListResponse> {
-    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
-    let voter = start_after
-        .map(|v| deps.api.addr_validate(&v).ok())
-        .flatten();
-    let prop = PROPOSALS.load(deps.storage, proposal_id.into())?;
+pub fn execute_update_members(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    add: Vec<Voter>,
+    remove: Vec<String>,
+) -> Result<Response<CustomMsg>, ContractError> {
+    // only the multisig itself can change its members, i.e. through a passed proposal
+    if info.sender != env.contract.address {
+        return Err(ContractError::Unauthorized {});
+    }
+    let mut cfg = CONFIG.load(deps.storage)?;
+    if cfg.group.is_some() {
+        return Err(ContractError::GroupMembers {});
+    }
 
-    let skip_elements = if let Some(voter) = voter {
-        prop.voters
-            .iter()
-            .position(|x| x == &voter)
-            .unwrap_or_default()
-    } else {
-        0
-    };
-    let votes: StdResult<Vec<_>> = prop
-        .voters
-        .iter()
-        .skip(skip_elements)
-        .take(limit)
-        .map(|addr| {
-            Ok(VoteInfo {
-                voter: addr.into(),
-                vote: Vote::Yes,
-                weight: 0,
-            })
-        })
+    for addr in remove.iter() {
+        let key = deps.api.addr_validate(addr)?;
+        VOTERS.remove(deps.storage, &key, env.block.height)?;
+    }
+    // adding an existing voter overwrites their weight
+    for voter in add.iter() {
+        if voter.weight == 0 {
+            return Err(ContractError::VoterZeroWeight {});
+        }
+        let key = deps.api.addr_validate(&voter.addr)?;
+        VOTERS.save(deps.storage, &key, &voter.weight, env.block.height)?;
+    }
+
+    // recompute the total weight of the resulting voter set
+    let weights: StdResult<Vec<u64>> = VOTERS
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| Ok(item?.1))
         .collect();
+    let weights = weights?;
+    if weights.is_empty() {
+        return Err(ContractError::NoVoters {});
+    }
+    let total_weight: u64 = weights.iter().sum();
 
-    Ok(VoteListResponse { votes: votes? })
+    cfg.threshold.validate(total_weight)?;
+    cfg.total_weight = total_weight;
+    CONFIG.save(deps.storage, &cfg)?;
+    TOTAL_WEIGHT.save(
+        deps.storage,
+        TOTAL_WEIGHT_KEY,
+        &total_weight,
+        env.block.height,
+    )?;
+
+    Ok(Response::new()
+        .add_attribute("action", "update_members")
+        .add_attribute("sender", info.sender)
+        .add_attribute("total_weight", total_weight.to_string()))
 }
 
-fn query_voter(deps: Deps, voter: String) -> StdResult<VoterResponse> {
-    let voter = deps.api.addr_validate(&voter)?;
-    let weight = VOTERS.may_load(deps.storage, &voter)?;
-    Ok(VoterResponse { weight })
+pub fn execute_update_config(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    update: ConfigUpdate,
+) -> Result<Response<CustomMsg>, ContractError> {
+    // only the multisig itself can change its rules, i.e. through a passed proposal
+    if info.sender != env.contract.address {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let mut cfg = CONFIG.load(deps.storage)?;
+    if let Some(threshold) = update.threshold {
+        threshold.validate(cfg.current_total_weight(deps.as_ref())?)?;
+        cfg.threshold = threshold;
+    }
+    if let Some(max_voting_period) = update.max_voting_period {
+        cfg.max_voting_period = max_voting_period;
+    }
+    if let Some(execution_delay) = update.execution_delay {
+        cfg.execution_delay = execution_delay;
+    }
+    if let Some(execution_window) = update.execution_window {
+        cfg.execution_window = execution_window;
+    }
+    if let Some(executor) = update.executor {
+        cfg.executor = executor;
+    }
+    if let Some(proposer) = update.proposer {
+        cfg.proposer = proposer;
+    }
+    if let Some(deposit) = update.deposit {
+        if let Some(deposit) = &deposit {
+            deposit.validate()?;
+        }
+        cfg.deposit = deposit;
+    }
+    if let Some(guard) = update.guard {
+        cfg.guard = guard
+            .map(|guard| deps.api.addr_validate(&guard))
+            .transpose()?;
+    }
+    if let Some(track_failures) = update.track_failures {
+        cfg.track_failures = track_failures;
+    }
+    if let Some(reject_overcommitted) = update.reject_overcommitted {
+        cfg.reject_overcommitted = reject_overcommitted;
+    }
+    // the periods are checked together, as changing one can invalidate the others
+    validate_execution_period(cfg.max_voting_period, cfg.execution_delay)?;
+    validate_execution_period(cfg.max_voting_period, cfg.execution_window)?;
+    validate_proposers(deps.storage, cfg.proposer)?;
+    CONFIG.save(deps.storage, &cfg)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "update_config")
+        .add_attribute("sender", info.sender)
+        .add_attribute("max_voting_period", cfg.max_voting_period.to_string()))
 }
 
-fn list_voters(
+pub fn execute_update_proposers(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    add: Vec<String>,
+    remove: Vec<String>,
+) -> Result<Response<CustomMsg>, ContractError> {
+    // only the multisig itself can change its proposers, i.e. through a passed proposal
+    if info.sender != env.contract.address {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    for addr in remove.iter() {
+        let key = deps.api.addr_validate(addr)?;
+        PROPOSERS.remove(deps.storage, &key);
+    }
+    for addr in add.iter() {
+        let key = deps.api.addr_validate(addr)?;
+        PROPOSERS.save(deps.storage, &key, &Empty {})?;
+    }
+    let cfg = CONFIG.load(deps.storage)?;
+    validate_proposers(deps.storage, cfg.proposer)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "update_proposers")
+        .add_attribute("sender", info.sender))
+}
+
+pub fn execute_enable_module(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    module: String,
+) -> Result<Response<CustomMsg>, ContractError> {
+    // only the multisig itself can enable modules, i.e. through a passed proposal
+    if info.sender != env.contract.address {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let module = deps.api.addr_validate(&module)?;
+    MODULES.save(deps.storage, &module, &Empty {})?;
+
+    Ok(Response::new()
+        .add_attribute("action", "enable_module")
+        .add_attribute("sender", info.sender)
+        .add_attribute("module", module))
+}
+
+pub fn execute_disable_module(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    module: String,
+) -> Result<Response<CustomMsg>, ContractError> {
+    // only the multisig itself can disable modules, i.e. through a passed proposal
+    if info.sender != env.contract.address {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let module = deps.api.addr_validate(&module)?;
+    MODULES.remove(deps.storage, &module);
+
+    Ok(Response::new()
+        .add_attribute("action", "disable_module")
+        .add_attribute("sender", info.sender)
+        .add_attribute("module", module))
+}
+
+pub fn execute_from_module(
     deps: Deps,
-    start_after: Option<String>,
-    limit: Option<u32>,
-) -> StdResult<VoterListResponse> {
-    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
-    let start = start_after.map(Bound::exclusive);
+    env: Env,
+    info: MessageInfo,
+    msgs: Vec<CosmosMsg<CustomMsg>>,
+) -> Result<Response<CustomMsg>, ContractError> {
+    // enabled modules act on behalf of the multisig without a vote
+    if !MODULES.has(deps.storage, &info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+    let cfg = CONFIG.load(deps.storage)?;
+    // like proposals, they cannot spend what is committed to proposals if configured so
+    if cfg.reject_overcommitted {
+        check_uncommitted(deps, &env, &msgs)?;
+    }
+    check_guard(deps, &cfg, &info.sender, &msgs)?;
 
-    let voters: StdResult<Vec<_>> = VOTERS
-        .range(deps.storage, start, None, Order::Ascending)
-        .take(limit)
-        .map(|item| {
-            let (key, weight) = item?;
-            Ok(VoterDetail {
-                addr: String::from_utf8(key)?,
-                weight,
-            })
-        })
-        .collect();
+    Ok(Response::new()
+        .add_messages(msgs)
+        .add_attribute("action", "execute_from_module")
+        .add_attribute("sender", info.sender))
+}
 
-    Ok(VoterListResponse { voters: voters? })
+pub fn execute_add_hook(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    addr: String,
+) -> Result<Response<CustomMsg>, ContractError> {
+    // only the multisig itself can register hooks, i.e. through a passed proposal
+    if info.sender != env.contract.address {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let addr = deps.api.addr_validate(&addr)?;
+    let mut hooks = HOOKS.may_load(deps.storage)?.unwrap_or_default();
+    if hooks.contains(&addr) {
+        return Err(ContractError::HookAlreadyRegistered {});
+    }
+    hooks.push(addr.clone());
+    HOOKS.save(deps.storage, &hooks)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "add_hook")
+        .add_attribute("sender", info.sender)
+        .add_attribute("hook", addr))
 }
 
-#[cfg(test)]
-mod tests {
-    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
-    use cosmwasm_std::{coin, from_binary, BankMsg};
+pub fn execute_remove_hook(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    addr: String,
+) -> Result<Response<CustomMsg>, ContractError> {
+    // only the multisig itself can remove hooks, i.e. through a passed proposal
+    if info.sender != env.contract.address {
+        return Err(ContractError::Unauthorized {});
+    }
 
-    use crate::expiration::Duration;
-    use crate::msg::Voter;
+    let addr = deps.api.addr_validate(&addr)?;
+    let mut hooks = HOOKS.may_load(deps.storage)?.unwrap_or_default();
+    let pos = hooks
+        .iter()
+        .position(|hook| hook == &addr)
+        .ok_or(ContractError::HookNotRegistered {})?;
+    hooks.remove(pos);
+    HOOKS.save(deps.storage, &hooks)?;
 
-    use super::*;
+    Ok(Response::new()
+        .add_attribute("action", "remove_hook")
+        .add_attribute("sender", info.sender)
+        .add_attribute("hook", addr))
+}
 
-    fn mock_env_height(height_delta: u64) -> Env {
-        let mut env = mock_env();
-        env.block.height += height_delta;
-        env
+pub fn execute_set_allowance(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    spender: String,
+    amount: Coin,
+    reset_period: Duration,
+) -> Result<Response<CustomMsg>, ContractError> {
+    // only the multisig itself can grant allowances, i.e. through a passed proposal
+    if info.sender != env.contract.address {
+        return Err(ContractError::Unauthorized {});
+    }
+    let empty_period = matches!(reset_period, Duration::Height(0) | Duration::Time(0));
+    if amount.amount.is_zero() || empty_period {
+        return Err(ContractError::InvalidAllowance {});
     }
 
-    fn mock_env_time(time_delta: u64) -> Env {
-        let mut env = mock_env();
-        env.block.time = env.block.time.plus_seconds(time_delta);
-        env
+    // a new allowance starts with a fresh period
+    let spender = deps.api.addr_validate(&spender)?;
+    let allowance = Allowance::new(amount.amount, reset_period, &env.block);
+    ALLOWANCES.save(deps.storage, (&spender, &amount.denom), &allowance)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "set_allowance")
+        .add_attribute("sender", info.sender)
+        .add_attribute("spender", spender)
+        .add_attribute("amount", amount.to_string()))
+}
+
+pub fn execute_remove_allowance(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    spender: String,
+    denom: String,
+) -> Result<Response<CustomMsg>, ContractError> {
+    // only the multisig itself can revoke allowances, i.e. through a passed proposal
+    if info.sender != env.contract.address {
+        return Err(ContractError::Unauthorized {});
     }
 
-    const OWNER: &str = "admin0001";
-    const VOTER1: &str = "voter0001";
-    const VOTER2: &str = "voter0002";
-    const VOTER3: &str = "voter0003";
-    const VOTER4: &str = "voter0004";
-    const VOTER5: &str = "voter0005";
-    const SOMEBODY: &str = "somebody";
+    let spender = deps.api.addr_validate(&spender)?;
+    ALLOWANCES.remove(deps.storage, (&spender, &denom));
 
-    fn voter<T: Into<String>>(addr: T, weight: u64) -> Voter {
-        Voter {
-            addr: addr.into(),
-            weight,
+    Ok(Response::new()
+        .add_attribute("action", "remove_allowance")
+        .add_attribute("sender", info.sender)
+        .add_attribute("spender", spender)
+        .add_attribute("denom", denom))
+}
+
+pub fn execute_spend_allowance(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    to: String,
+    amount: Coin,
+) -> Result<Response<CustomMsg>, ContractError> {
+    // allowances are paid out of the treasury, the spender has nothing to send along
+    if !info.funds.is_empty() {
+        return Err(ContractError::UnexpectedFunds {});
+    }
+    if amount.amount.is_zero() {
+        return Err(ContractError::ZeroAmount {});
+    }
+    // only addresses with an allowance for the denom can spend without a vote
+    let mut allowance = ALLOWANCES
+        .may_load(deps.storage, (&info.sender, &amount.denom))?
+        .ok_or(ContractError::Unauthorized {})?
+        .current(&env.block);
+    let remaining = allowance.remaining();
+    if amount.amount > remaining {
+        return Err(ContractError::AllowanceExceeded {
+            remaining: Coin::new(remaining.u128(), amount.denom).to_string(),
+        });
+    }
+    allowance.spent += amount.amount;
+    ALLOWANCES.save(deps.storage, (&info.sender, &amount.denom), &allowance)?;
+
+    let to = deps.api.addr_validate(&to)?;
+    let msgs: Vec<CosmosMsg<CustomMsg>> = vec![BankMsg::Send {
+        to_address: to.to_string(),
+        amount: vec![amount.clone()],
+    }
+    .into()];
+    let cfg = CONFIG.load(deps.storage)?;
+    if cfg.reject_overcommitted {
+        check_uncommitted(deps.as_ref(), &env, &msgs)?;
+    }
+    check_guard(deps.as_ref(), &cfg, &info.sender, &msgs)?;
+
+    Ok(Response::new()
+        .add_messages(msgs)
+        .add_attribute("action", "spend_allowance")
+        .add_attribute("sender", info.sender)
+        .add_attribute("to", to)
+        .add_attribute("amount", amount.to_string()))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn reply(deps: DepsMut, _env: Env, msg: Reply) -> Result<Response<CustomMsg>, ContractError> {
+    match (msg.id, msg.result) {
+        // a failing hook contract must not block the multisig, so its error is only recorded
+        (HOOK_REPLY_ID, ContractResult::Err(error)) => Ok(Response::new()
+            .add_attribute("action", "hook_failed")
+            .add_attribute("error", error)),
+        // proposal messages are dispatched with the proposal id, only reporting back failures
+        // when they are tracked, and the results of the individual messages otherwise
+        (proposal_id, ContractResult::Err(error)) => {
+            record_execution_failure(deps, proposal_id, error)
+        }
+        (proposal_id, ContractResult::Ok(receipt)) => {
+            record_execution_receipt(deps, proposal_id, receipt)
         }
     }
+}
 
-    // this will set up the instantiation for other tests
-    fn setup_test_case(
-        deps: DepsMut,
-        info: MessageInfo,
-        threshold_weight: u64,
-        max_voting_period: Duration,
-    ) -> Result<Response<Empty>, ContractError> {
-        // Instantiate a contract with voters
-        let voters = vec![
-            voter(&info.sender, 1),
-            voter(VOTER1, 1),
-            voter(VOTER2, 2),
-            voter(VOTER3, 3),
-            voter(VOTER4, 4),
-            voter(VOTER5, 5),
-        ];
+fn record_execution_receipt(
+    deps: DepsMut,
+    proposal_id: u64,
+    receipt: SubMsgExecutionResponse,
+) -> Result<Response<CustomMsg>, ContractError> {
+    if !PROPOSALS.has(deps.storage, proposal_id.into()) {
+        return Err(ContractError::UnknownReplyId { id: proposal_id });
+    }
+    // replies arrive in message order
+    let mut receipts = EXECUTION_RECEIPTS
+        .may_load(deps.storage, proposal_id.into())?
+        .unwrap_or_default();
+    receipts.push(receipt);
+    EXECUTION_RECEIPTS.save(deps.storage, proposal_id.into(), &receipts)?;
 
-        let instantiate_msg = InstantiateMsg {
-            voters,
-            threshold_weight,
-            max_voting_period,
-        };
-        instantiate(deps, mock_env(), info, instantiate_msg)
+    Ok(Response::new()
+        .add_attribute("action", "execution_receipt")
+        .add_attribute("proposal_id", proposal_id.to_string())
+        .add_attribute("index", (receipts.len() - 1).to_string()))
+}
+
+fn record_execution_failure(
+    deps: DepsMut,
+    proposal_id: u64,
+    error: String,
+) -> Result<Response<CustomMsg>, ContractError> {
+    let mut prop = PROPOSALS
+        .may_load(deps.storage, proposal_id.into())?
+        .ok_or(ContractError::UnknownReplyId { id: proposal_id })?;
+    // a failed dispatch is reverted, so the proposal is still the one being dispatched
+    if DISPATCHING.may_load(deps.storage)? != Some(proposal_id) {
+        return Err(ContractError::UnknownReplyId { id: proposal_id });
+    }
+    if prop.status == Status::Executed {
+        return Err(ContractError::WrongExecuteStatus {});
     }
+    let old_status = prop.status;
+    prop.status = Status::ExecutionFailed;
+    PROPOSALS.save(deps.storage, proposal_id.into(), &prop)?;
+    update_committed(deps.storage, &prop, old_status)?;
+    EXECUTION_FAILURES.save(deps.storage, proposal_id.into(), &error)?;
+    DISPATCHING.remove(deps.storage);
+    let hooks = status_changed_hooks(deps.storage, proposal_id, old_status, prop.status)?;
 
-    fn get_tally(deps: Deps, proposal_id: u64) -> u64 {
-        // Get all the voters on the proposal
-        let voters = QueryMsg::ListVotes {
+    Ok(Response::new()
+        .add_submessages(hooks)
+        .add_attribute("action", "execution_failed")
+        .add_attribute("proposal_id", proposal_id.to_string())
+        .add_attribute("error", error))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::Threshold {} => to_binary(&query_threshold(deps)?),
+        QueryMsg::Proposal { proposal_id } => to_binary(&query_proposal(deps, env, proposal_id)?),
+        QueryMsg::Vote { proposal_id, voter } => to_binary(&query_vote(deps, proposal_id, voter)?),
+        QueryMsg::ListProposals { start_after, limit } => {
+            to_binary(&list_proposals(deps, env, start_after, limit)?)
+        }
+        QueryMsg::ReverseProposals {
+            start_before,
+            limit,
+        } => to_binary(&reverse_proposals(deps, env, start_before, limit)?),
+        QueryMsg::ListVotes {
             proposal_id,
-            start_after: None,
-            limit: None,
-        };
-        let votes: VoteListResponse =
-            from_binary(&query(deps, mock_env(), voters).unwrap()).unwrap();
-        // Sum the weights of the Yes votes to get the tally
-        votes
-            .votes
-            .iter()
-            .filter(|&v| v.vote == Vote::Yes)
-            .map(|v| v.weight)
-            .sum()
+            start_after,
+            limit,
+        } => to_binary(&list_votes(deps, proposal_id, start_after, limit)?),
+        QueryMsg::Voter { address } => to_binary(&query_voter(deps, address)?),
+        QueryMsg::ListVoters { start_after, limit } => {
+            to_binary(&list_voters(deps, start_after, limit)?)
+        }
+        QueryMsg::Config {} => to_binary(&query_config(deps)?),
+        QueryMsg::ListProposers { start_after, limit } => {
+            to_binary(&list_proposers(deps, start_after, limit)?)
+        }
+        QueryMsg::ListModules { start_after, limit } => {
+            to_binary(&list_modules(deps, start_after, limit)?)
+        }
+        QueryMsg::ListHooks {} => to_binary(&list_hooks(deps)?),
+        QueryMsg::ProposalStatus { proposal_id } => {
+            to_binary(&query_proposal_status(deps, env, proposal_id)?)
+        }
+        QueryMsg::ExecutionFailure { proposal_id } => {
+            to_binary(&query_execution_failure(deps, proposal_id)?)
+        }
+        QueryMsg::ExecutionReceipt { proposal_id } => {
+            to_binary(&query_execution_receipt(deps, proposal_id)?)
+        }
+        QueryMsg::Allowance { spender, denom } => {
+            to_binary(&query_allowance(deps, env, spender, denom)?)
+        }
+        QueryMsg::ListAllowances {
+            spender,
+            start_after,
+            limit,
+        } => to_binary(&list_allowances(deps, env, spender, start_after, limit)?),
+        QueryMsg::Treasury {} => to_binary(&query_treasury(deps, env)?),
+    }
+}
+
+fn query_threshold(deps: Deps) -> StdResult<ThresholdResponse> {
+    let cfg = CONFIG.load(deps.storage)?;
+    let total_weight = cfg.current_total_weight(deps)?;
+    Ok(cfg.threshold.to_response(total_weight))
+}
+
+fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
+    let cfg = CONFIG.load(deps.storage)?;
+    let total_weight = cfg.current_total_weight(deps)?;
+    Ok(ConfigResponse {
+        threshold: cfg.threshold.to_response(total_weight),
+        max_voting_period: cfg.max_voting_period,
+        execution_delay: cfg.execution_delay,
+        execution_window: cfg.execution_window,
+        executor: cfg.executor,
+        proposer: cfg.proposer,
+        deposit: cfg.deposit,
+        guard: cfg.guard,
+        group: cfg.group,
+        track_failures: cfg.track_failures,
+        reject_overcommitted: cfg.reject_overcommitted,
+    })
+}
+
+fn query_proposal(deps: Deps, env: Env, id: u64) -> StdResult<ProposalResponse<CustomMsg>> {
+    let prop = PROPOSALS.load(deps.storage, id.into())?;
+    let status = prop.current_status(&env.block).to_cw3();
+    let threshold = prop.threshold.to_response(prop.total_weight);
+
+    Ok(ProposalResponse {
+        id,
+        title: prop.title,
+        description: prop.description,
+        msgs: prop.msgs,
+        status,
+        expires: prop.expires,
+        threshold,
+    })
+}
+
+// settings for pagination
+const MAX_LIMIT: u32 = 30;
+const DEFAULT_LIMIT: u32 = 10;
+
+fn list_proposals(
+    deps: Deps,
+    env: Env,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<ProposalListResponse<CustomMsg>> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive_int);
+    let props: StdResult<Vec<_>> = PROPOSALS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|p| map_proposal(&env.block, p))
+        .collect();
+
+    Ok(ProposalListResponse { proposals: props? })
+}
+
+fn reverse_proposals(
+    deps: Deps,
+    env: Env,
+    start_before: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<ProposalListResponse<CustomMsg>> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let end = start_before.map(Bound::exclusive_int);
+    let props: StdResult<Vec<_>> = PROPOSALS
+        .range(deps.storage, None, end, Order::Descending)
+        .take(limit)
+        .map(|p| map_proposal(&env.block, p))
+        .collect();
+
+    Ok(ProposalListResponse { proposals: props? })
+}
+
+fn map_proposal(
+    block: &BlockInfo,
+    item: StdResult<(Vec<u8>, Proposal<CustomMsg>)>,
+) -> StdResult<ProposalResponse<CustomMsg>> {
+    let (key, prop) = item?;
+    let status = prop.current_status(block).to_cw3();
+    let threshold = prop.threshold.to_response(prop.total_weight);
+    Ok(ProposalResponse {
+        id: parse_id(&key)?,
+        title: prop.title,
+        description: prop.description,
+        msgs: prop.msgs,
+        status,
+        expires: prop.expires,
+        threshold,
+    })
+}
+
+fn query_proposal_status(deps: Deps, env: Env, id: u64) -> StdResult<ProposalStatusResponse> {
+    let prop = PROPOSALS.load(deps.storage, id.into())?;
+    let status = prop.current_status(&env.block);
+    Ok(ProposalStatusResponse { status })
+}
+
+fn query_execution_failure(deps: Deps, proposal_id: u64) -> StdResult<ExecutionFailureResponse> {
+    // ensure the proposal exists
+    PROPOSALS.load(deps.storage, proposal_id.into())?;
+
+    let error = EXECUTION_FAILURES.may_load(deps.storage, proposal_id.into())?;
+    Ok(ExecutionFailureResponse { error })
+}
+
+fn query_execution_receipt(deps: Deps, proposal_id: u64) -> StdResult<ExecutionReceiptResponse> {
+    // ensure the proposal exists
+    PROPOSALS.load(deps.storage, proposal_id.into())?;
+
+    let receipts = EXECUTION_RECEIPTS
+        .may_load(deps.storage, proposal_id.into())?
+        .unwrap_or_default();
+    Ok(ExecutionReceiptResponse { receipts })
+}
+
+fn query_vote(deps: Deps, proposal_id: u64, voter: String) -> StdResult<VoteResponse> {
+    let voter = deps.api.addr_validate(&voter)?;
+    // ensure the proposal exists
+    PROPOSALS.load(deps.storage, proposal_id.into())?;
+
+    let ballot = BALLOTS.may_load(deps.storage, (proposal_id.into(), &voter))?;
+    let vote = ballot.map(|b| VoteInfo {
+        voter: voter.into(),
+        vote: b.vote,
+        weight: b.weight,
+    });
+    Ok(VoteResponse { vote })
+}
+
+fn list_votes(
+    deps: Deps,
+    proposal_id: u64,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<VoteListResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let votes: StdResult<Vec<_>> = BALLOTS
+        .prefix(proposal_id.into())
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (key, ballot) = item?;
+            Ok(VoteInfo {
+                voter: String::from_utf8(key)?,
+                vote: ballot.vote,
+                weight: ballot.weight,
+            })
+        })
+        .collect();
+
+    Ok(VoteListResponse { votes: votes? })
+}
+
+fn query_voter(deps: Deps, voter: String) -> StdResult<VoterResponse> {
+    let voter = deps.api.addr_validate(&voter)?;
+    let cfg = CONFIG.load(deps.storage)?;
+    let weight = cfg.voter_weight(deps, &voter, None)?;
+    Ok(VoterResponse { weight })
+}
+
+fn list_voters(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<VoterListResponse> {
+    // with a group, its members are the voters
+    if let Some(group) = CONFIG.load(deps.storage)?.group {
+        let query = Cw4QueryMsg::ListMembers { start_after, limit };
+        let res: MemberListResponse = deps.querier.query_wasm_smart(group, &query)?;
+        let voters = res
+            .members
+            .into_iter()
+            .map(|member| VoterDetail {
+                addr: member.addr,
+                weight: member.weight,
+            })
+            .collect();
+        return Ok(VoterListResponse { voters });
+    }
+
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let voters: StdResult<Vec<_>> = VOTERS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (key, weight) = item?;
+            Ok(VoterDetail {
+                addr: String::from_utf8(key)?,
+                weight,
+            })
+        })
+        .collect();
+
+    Ok(VoterListResponse { voters: voters? })
+}
+
+fn list_proposers(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<ProposerListResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let proposers: StdResult<Vec<_>> = PROPOSERS
+        .keys(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|key| Ok(String::from_utf8(key)?))
+        .collect();
+
+    Ok(ProposerListResponse {
+        proposers: proposers?,
+    })
+}
+
+fn list_modules(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<ModuleListResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let modules: StdResult<Vec<_>> = MODULES
+        .keys(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|key| Ok(String::from_utf8(key)?))
+        .collect();
+
+    Ok(ModuleListResponse { modules: modules? })
+}
+
+fn list_hooks(deps: Deps) -> StdResult<HooksResponse> {
+    let hooks = HOOKS.may_load(deps.storage)?.unwrap_or_default();
+    Ok(HooksResponse {
+        hooks: hooks.into_iter().map(String::from).collect(),
+    })
+}
+
+fn query_treasury(deps: Deps, env: Env) -> StdResult<TreasuryResponse> {
+    let balances = deps.querier.query_all_balances(&env.contract.address)?;
+    let committed = load_totals(deps.storage, &COMMITTED)?;
+    let deposits = load_totals(deps.storage, &HELD_DEPOSITS)?;
+    let total_of = |totals: &[Coin], denom: &str| {
+        totals
+            .iter()
+            .find(|total| total.denom == denom)
+            .map(|total| total.amount)
+            .unwrap_or_default()
+    };
+    let uncommitted = balances
+        .iter()
+        .map(|balance| Coin {
+            denom: balance.denom.clone(),
+            amount: balance
+                .amount
+                .saturating_sub(total_of(&committed, &balance.denom))
+                .saturating_sub(total_of(&deposits, &balance.denom)),
+        })
+        .collect();
+
+    Ok(TreasuryResponse {
+        balances,
+        committed,
+        deposits,
+        uncommitted,
+    })
+}
+
+fn load_totals(storage: &dyn Storage, totals: &Map<&str, Uint128>) -> StdResult<Vec<Coin>> {
+    totals
+        .range(storage, None, None, Order::Ascending)
+        .map(|item| {
+            let (denom, amount) = item?;
+            Ok(Coin {
+                denom: String::from_utf8(denom)?,
+                amount,
+            })
+        })
+        .collect()
+}
+
+fn query_allowance(
+    deps: Deps,
+    env: Env,
+    spender: String,
+    denom: String,
+) -> StdResult<AllowanceResponse> {
+    let spender = deps.api.addr_validate(&spender)?;
+    let allowance = ALLOWANCES
+        .may_load(deps.storage, (&spender, &denom))?
+        .map(|allowance| map_allowance(&env.block, denom, allowance));
+    Ok(AllowanceResponse { allowance })
+}
+
+fn list_allowances(
+    deps: Deps,
+    env: Env,
+    spender: String,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<AllowanceListResponse> {
+    let spender = deps.api.addr_validate(&spender)?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let allowances: StdResult<Vec<_>> = ALLOWANCES
+        .prefix(&spender)
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (key, allowance) = item?;
+            Ok(map_allowance(
+                &env.block,
+                String::from_utf8(key)?,
+                allowance,
+            ))
+        })
+        .collect();
+
+    Ok(AllowanceListResponse {
+        allowances: allowances?,
+    })
+}
+
+fn map_allowance(block: &BlockInfo, denom: String, allowance: Allowance) -> AllowanceInfo {
+    let allowance = allowance.current(block);
+    AllowanceInfo {
+        amount: Coin::new(allowance.amount.u128(), &denom),
+        remaining: Coin::new(allowance.remaining().u128(), denom),
+        reset_period: allowance.reset_period,
+        resets: allowance.resets,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::testing::{
+        mock_dependencies, mock_env, mock_info, MockApi, MockQuerier, MockStorage,
+        MOCK_CONTRACT_ADDR,
+    };
+    use cosmwasm_std::{
+        coin, from_binary, from_slice, BankMsg, ContractResult, Decimal, Event, OwnedDeps, Querier,
+        QuerierResult, QueryRequest, SubMsg, SystemError, SystemResult, WasmMsg, WasmQuery,
+    };
+
+    use super::*;
+    use crate::cw4::{Member, MemberResponse};
+    use crate::migrations::{ConfigV0_1, ProposalV0_1, CONFIG_V0_1, PROPOSALS_V0_1};
+    use crate::msg::Threshold;
+    use crate::state::PassedAt;
+
+    fn mock_env_height(height_delta: u64) -> Env {
+        let mut env = mock_env();
+        env.block.height += height_delta;
+        env
+    }
+
+    fn mock_env_time(time_delta: u64) -> Env {
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(time_delta);
+        env
+    }
+
+    const OWNER: &str = "admin0001";
+    const VOTER1: &str = "voter0001";
+    const VOTER2: &str = "voter0002";
+    const VOTER3: &str = "voter0003";
+    const VOTER4: &str = "voter0004";
+    const VOTER5: &str = "voter0005";
+    const SOMEBODY: &str = "somebody";
+
+    /// Answers a smart query to the given contract
+    type SmartQueryHandler = Box<dyn Fn(&str, &Binary) -> StdResult<Binary>>;
+
+    /// A querier that answers smart queries to other contracts with `handler`
+    struct WasmMockQuerier {
+        base: MockQuerier,
+        handler: SmartQueryHandler,
+    }
+
+    impl Querier for WasmMockQuerier {
+        fn raw_query(&self, bin_request: &[u8]) -> QuerierResult {
+            let request: QueryRequest<Empty> = match from_slice(bin_request) {
+                Ok(v) => v,
+                Err(e) => {
+                    return SystemResult::Err(SystemError::InvalidRequest {
+                        error: format!("Parsing query request: {}", e),
+                        request: bin_request.into(),
+                    })
+                }
+            };
+            match request {
+                QueryRequest::Wasm(WasmQuery::Smart { contract_addr, msg }) => {
+                    SystemResult::Ok(ContractResult::from((self.handler)(&contract_addr, &msg)))
+                }
+                _ => self.base.handle_query(&request),
+            }
+        }
+    }
+
+    fn mock_dependencies_with_wasm(
+        handler: impl Fn(&str, &Binary) -> StdResult<Binary> + 'static,
+    ) -> OwnedDeps<MockStorage, MockApi, WasmMockQuerier> {
+        OwnedDeps {
+            storage: MockStorage::default(),
+            api: MockApi::default(),
+            querier: WasmMockQuerier {
+                base: MockQuerier::new(&[]),
+                handler: Box::new(handler),
+            },
+        }
+    }
+
+    fn voter<T: Into<String>>(addr: T, weight: u64) -> Voter {
+        Voter {
+            addr: addr.into(),
+            weight,
+        }
+    }
+
+    // an instantiate message with the defaults of every optional setting
+    fn init_msg(
+        voters: Vec<Voter>,
+        threshold: Threshold,
+        max_voting_period: Duration,
+    ) -> InstantiateMsg {
+        InstantiateMsg {
+            voters,
+            group: None,
+            threshold,
+            max_voting_period,
+            execution_delay: None,
+            execution_window: None,
+            executor: Executor::default(),
+            proposer: Proposer::default(),
+            proposers: vec![],
+            deposit: None,
+            guard: None,
+            track_failures: false,
+            reject_overcommitted: false,
+        }
+    }
+
+    // this will set up the instantiation for other tests
+    fn setup_test_case(
+        deps: DepsMut,
+        info: MessageInfo,
+        threshold_weight: u64,
+        max_voting_period: Duration,
+    ) -> Result<Response<CustomMsg>, ContractError> {
+        // Instantiate a contract with voters
+        let voters = vec![
+            voter(&info.sender, 1),
+            voter(VOTER1, 1),
+            voter(VOTER2, 2),
+            voter(VOTER3, 3),
+            voter(VOTER4, 4),
+            voter(VOTER5, 5),
+        ];
+
+        let instantiate_msg = init_msg(
+            voters,
+            Threshold::AbsoluteCount {
+                weight: threshold_weight,
+            },
+            max_voting_period,
+        );
+        instantiate(deps, mock_env(), info, instantiate_msg)
+    }
+
+    fn get_tally(deps: Deps, proposal_id: u64) -> u64 {
+        // Get all the voters on the proposal
+        let voters = QueryMsg::ListVotes {
+            proposal_id,
+            start_after: None,
+            limit: None,
+        };
+        let votes: VoteListResponse =
+            from_binary(&query(deps, mock_env(), voters).unwrap()).unwrap();
+        // Sum the weights of the Yes votes to get the tally
+        votes
+            .votes
+            .iter()
+            .filter(|&v| v.vote == Vote::Yes)
+            .map(|v| v.weight)
+            .sum()
+    }
+
+    #[test]
+    fn test_instantiate_works() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info(OWNER, &[]);
+
+        let max_voting_period = Duration::Time(1234567);
+
+        // No voters fails
+        let instantiate_msg = init_msg(
+            vec![],
+            Threshold::AbsoluteCount { weight: 1 },
+            max_voting_period,
+        );
+        let err =
+            instantiate(deps.as_mut(), mock_env(), info.clone(), instantiate_msg).unwrap_err();
+        assert_eq!(err, ContractError::NoVoters {});
+
+        // Zero required weight fails
+        let instantiate_msg = init_msg(
+            vec![voter(OWNER, 1)],
+            Threshold::AbsoluteCount { weight: 0 },
+            max_voting_period,
+        );
+        let err =
+            instantiate(deps.as_mut(), mock_env(), info.clone(), instantiate_msg).unwrap_err();
+        assert_eq!(err, ContractError::ZeroWeight {});
+
+        // Total weight less than required weight not allowed
+        let threshold_weight = 100;
+        let err = setup_test_case(
+            deps.as_mut(),
+            info.clone(),
+            threshold_weight,
+            max_voting_period,
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::UnreachableWeight {});
+
+        // All valid
+        let threshold_weight = 1;
+        setup_test_case(deps.as_mut(), info, threshold_weight, max_voting_period).unwrap();
+    }
+
+    // TODO: query() tests
+
+    #[test]
+    fn test_propose_works() {
+        let mut deps = mock_dependencies(&[]);
+
+        let threshold_weight = 4;
+        let voting_period = Duration::Time(2000000);
+
+        let info = mock_info(OWNER, &[]);
+        setup_test_case(deps.as_mut(), info, threshold_weight, voting_period).unwrap();
+
+        let bank_msg = BankMsg::Send {
+            to_address: SOMEBODY.into(),
+            amount: vec![coin(1, "BTC")],
+        };
+        let msgs = vec![CosmosMsg::Bank(bank_msg)];
+
+        // Wrong expiration option fails
+        let info = mock_info(OWNER, &[]);
+        let proposal_wrong_exp = ExecuteMsg::Propose {
+            title: "Rewarding somebody".to_string(),
+            description: "Do we reward her?".to_string(),
+            msgs: msgs.clone(),
+            earliest: None,
+            latest: Some(Expiration::AtHeight(123456)),
+        };
+        let err = execute(deps.as_mut(), mock_env(), info, proposal_wrong_exp).unwrap_err();
+        assert_eq!(err, ContractError::WrongExpiration {});
+
+        // Anyone can propose
+        let info = mock_info(SOMEBODY, &[]);
+        let proposal = ExecuteMsg::Propose {
+            title: "Rewarding somebody".to_string(),
+            description: "Do we reward her?".to_string(),
+            msgs: msgs.clone(),
+            earliest: None,
+            latest: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, proposal.clone()).unwrap();
+        assert_eq!(
+            res,
+            Response::new()
+                .add_attribute("action", "propose")
+                .add_attribute("sender", SOMEBODY)
+                .add_attribute("proposal_id", 1.to_string())
+                .add_attribute("status", "Open")
+        );
+
+        // Proposal from voter works
+        let info = mock_info(VOTER3, &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, proposal.clone()).unwrap();
+
+        // Verify
+        assert_eq!(
+            res,
+            Response::new()
+                .add_attribute("action", "propose")
+                .add_attribute("sender", VOTER3)
+                .add_attribute("proposal_id", 2.to_string())
+                .add_attribute("status", "Open")
+        );
+
+        // Proposal from voter with enough vote power directly passes
+        let info = mock_info(VOTER4, &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, proposal).unwrap();
+
+        // Verify
+        assert_eq!(
+            res,
+            Response::new()
+                .add_attribute("action", "propose")
+                .add_attribute("sender", VOTER4)
+                .add_attribute("proposal_id", 3.to_string())
+                .add_attribute("status", "Passed")
+        );
+    }
+
+    #[test]
+    fn test_vote_works() {
+        let mut deps = mock_dependencies(&[]);
+
+        let threshold_weight = 3;
+        let voting_period = Duration::Time(2000000);
+
+        let info = mock_info(OWNER, &[]);
+        setup_test_case(deps.as_mut(), info.clone(), threshold_weight, voting_period).unwrap();
+
+        // Propose
+        let bank_msg = BankMsg::Send {
+            to_address: SOMEBODY.into(),
+            amount: vec![coin(1, "BTC")],
+        };
+        let msgs = vec![CosmosMsg::Bank(bank_msg)];
+        let proposal = ExecuteMsg::Propose {
+            title: "Pay somebody".to_string(),
+            description: "Do I pay her?".to_string(),
+            msgs,
+            earliest: None,
+            latest: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), proposal).unwrap();
+
+        // Get the proposal id from the logs
+        let proposal_id: u64 = res.attributes[2].value.parse().unwrap();
+
+        // Owner cannot vote (again)
+        let yes_vote = ExecuteMsg::Vote {
+            proposal_id,
+            vote: Vote::Yes,
+        };
+        let err = execute(deps.as_mut(), mock_env(), info, yes_vote.clone()).unwrap_err();
+        assert_eq!(err, ContractError::AlreadyVoted {});
+
+        // Only voters can vote
+        let info = mock_info(SOMEBODY, &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, yes_vote.clone()).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        // But voter1 can
+        let info = mock_info(VOTER1, &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, yes_vote.clone()).unwrap();
+
+        // Verify
+        assert_eq!(
+            res,
+            Response::new()
+                .add_attribute("action", "vote")
+                .add_attribute("sender", VOTER1)
+                .add_attribute("proposal_id", proposal_id.to_string())
+                .add_attribute("status", "Open")
+        );
+
+        // No/Veto votes have no effect on the tally
+        // Get the proposal id from the logs
+        let proposal_id: u64 = res.attributes[2].value.parse().unwrap();
+
+        // Compute the current tally
+        let tally = get_tally(deps.as_ref(), proposal_id);
+
+        // Cast a No vote
+        let no_vote = ExecuteMsg::Vote {
+            proposal_id,
+            vote: Vote::No,
+        };
+        let info = mock_info(VOTER2, &[]);
+        execute(deps.as_mut(), mock_env(), info, no_vote.clone()).unwrap();
+
+        // Cast a Veto vote
+        let veto_vote = ExecuteMsg::Vote {
+            proposal_id,
+            vote: Vote::Veto,
+        };
+        let info = mock_info(VOTER3, &[]);
+        execute(deps.as_mut(), mock_env(), info.clone(), veto_vote).unwrap();
+
+        // Verify
+        assert_eq!(tally, get_tally(deps.as_ref(), proposal_id));
+
+        // Once voted, the same vote cannot be cast again
+        let info = mock_info(VOTER1, &[]);
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), yes_vote.clone()).unwrap_err();
+        assert_eq!(err, ContractError::AlreadyVoted {});
+        assert_eq!(tally, get_tally(deps.as_ref(), proposal_id));
+
+        // Expired proposals cannot be voted
+        let env = match voting_period {
+            Duration::Time(duration) => mock_env_time(duration + 1),
+            Duration::Height(duration) => mock_env_height(duration + 1),
+        };
+        let err = execute(deps.as_mut(), env, info, no_vote).unwrap_err();
+        assert_eq!(err, ContractError::Expired {});
+
+        // Vote it again, so it passes
+        let info = mock_info(VOTER4, &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, yes_vote.clone()).unwrap();
+
+        // Verify
+        assert_eq!(
+            res,
+            Response::new()
+                .add_attribute("action", "vote")
+                .add_attribute("sender", VOTER4)
+                .add_attribute("proposal_id", proposal_id.to_string())
+                .add_attribute("status", "Passed")
+        );
+
+        // non-Open proposals cannot be voted
+        let info = mock_info(VOTER5, &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, yes_vote).unwrap_err();
+        assert_eq!(err, ContractError::NotOpen {});
+    }
+
+    #[test]
+    fn test_execute_works() {
+        let mut deps = mock_dependencies(&[]);
+
+        let threshold_weight = 3;
+        let voting_period = Duration::Time(2000000);
+
+        let info = mock_info(OWNER, &[]);
+        setup_test_case(deps.as_mut(), info.clone(), threshold_weight, voting_period).unwrap();
+
+        // Propose
+        let bank_msg = BankMsg::Send {
+            to_address: SOMEBODY.into(),
+            amount: vec![coin(1, "BTC")],
+        };
+        let msgs = vec![CosmosMsg::Bank(bank_msg)];
+        let proposal = ExecuteMsg::Propose {
+            title: "Pay somebody".to_string(),
+            description: "Do I pay her?".to_string(),
+            msgs: msgs.clone(),
+            earliest: None,
+            latest: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), proposal).unwrap();
+
+        // Get the proposal id from the logs
+        let proposal_id: u64 = res.attributes[2].value.parse().unwrap();
+
+        // Only Passed can be executed
+        let execution = ExecuteMsg::Execute { proposal_id };
+        let err = execute(deps.as_mut(), mock_env(), info, execution.clone()).unwrap_err();
+        assert_eq!(err, ContractError::WrongExecuteStatus {});
+
+        // Vote it, so it passes
+        let vote = ExecuteMsg::Vote {
+            proposal_id,
+            vote: Vote::Yes,
+        };
+        let info = mock_info(VOTER3, &[]);
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), vote).unwrap();
+
+        // Verify
+        assert_eq!(
+            res,
+            Response::new()
+                .add_attribute("action", "vote")
+                .add_attribute("sender", VOTER3)
+                .add_attribute("proposal_id", proposal_id.to_string())
+                .add_attribute("status", "Passed")
+        );
+
+        // In passing: Try to close Passed fails
+        let closing = ExecuteMsg::Close { proposal_id };
+        let err = execute(deps.as_mut(), mock_env(), info, closing).unwrap_err();
+        assert_eq!(err, ContractError::WrongCloseStatus {});
+
+        // Execute works. Anybody can execute Passed proposals
+        let info = mock_info(SOMEBODY, &[]);
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), execution).unwrap();
+
+        // Verify
+        assert_eq!(
+            res,
+            Response::new()
+                .add_submessages(
+                    msgs.into_iter()
+                        .map(|msg| SubMsg::reply_on_success(msg, proposal_id))
+                )
+                .add_attribute("action", "execute")
+                .add_attribute("sender", SOMEBODY)
+                .add_attribute("proposal_id", proposal_id.to_string())
+        );
+
+        // In passing: Try to close Executed fails
+        let closing = ExecuteMsg::Close { proposal_id };
+        let err = execute(deps.as_mut(), mock_env(), info, closing).unwrap_err();
+        assert_eq!(err, ContractError::WrongCloseStatus {});
+    }
+
+    #[test]
+    fn test_close_works() {
+        let mut deps = mock_dependencies(&[]);
+
+        let threshold_weight = 3;
+        let voting_period = Duration::Height(2000000);
+
+        let info = mock_info(OWNER, &[]);
+        setup_test_case(deps.as_mut(), info.clone(), threshold_weight, voting_period).unwrap();
+
+        // Propose
+        let bank_msg = BankMsg::Send {
+            to_address: SOMEBODY.into(),
+            amount: vec![coin(1, "BTC")],
+        };
+        let msgs = vec![CosmosMsg::Bank(bank_msg)];
+        let proposal = ExecuteMsg::Propose {
+            title: "Pay somebody".to_string(),
+            description: "Do I pay her?".to_string(),
+            msgs: msgs.clone(),
+            earliest: None,
+            latest: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, proposal).unwrap();
+
+        // Get the proposal id from the logs
+        let proposal_id: u64 = res.attributes[2].value.parse().unwrap();
+
+        let closing = ExecuteMsg::Close { proposal_id };
+
+        // Anybody can close
+        let info = mock_info(SOMEBODY, &[]);
+
+        // Non-expired proposals cannot be closed
+        let err = execute(deps.as_mut(), mock_env(), info, closing).unwrap_err();
+        assert_eq!(err, ContractError::NotExpired {});
+
+        // Expired proposals can be closed
+        let info = mock_info(OWNER, &[]);
+
+        let proposal = ExecuteMsg::Propose {
+            title: "(Try to) pay somebody".to_string(),
+            description: "Pay somebody after time?".to_string(),
+            msgs,
+            earliest: None,
+            latest: Some(Expiration::AtHeight(123456)),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), proposal).unwrap();
+
+        // Get the proposal id from the logs
+        let proposal_id: u64 = res.attributes[2].value.parse().unwrap();
+
+        let closing = ExecuteMsg::Close { proposal_id };
+
+        // Close expired works
+        let env = mock_env_height(1234567);
+        let res = execute(
+            deps.as_mut(),
+            env,
+            mock_info(SOMEBODY, &[]),
+            closing.clone(),
+        )
+        .unwrap();
+
+        // Verify
+        assert_eq!(
+            res,
+            Response::new()
+                .add_attribute("action", "close")
+                .add_attribute("sender", SOMEBODY)
+                .add_attribute("proposal_id", proposal_id.to_string())
+        );
+
+        // Trying to close it again fails
+        let err = execute(deps.as_mut(), mock_env(), info, closing).unwrap_err();
+        assert_eq!(err, ContractError::WrongCloseStatus {});
+    }
+
+    #[test]
+    fn test_update_members_works() {
+        let mut deps = mock_dependencies(&[]);
+
+        let threshold_weight = 3;
+        let voting_period = Duration::Time(2000000);
+
+        let info = mock_info(OWNER, &[]);
+        setup_test_case(deps.as_mut(), info.clone(), threshold_weight, voting_period).unwrap();
+
+        let update = ExecuteMsg::UpdateMembers {
+            add: vec![voter(VOTER1, 7), voter(SOMEBODY, 2)],
+            remove: vec![VOTER5.into()],
+        };
+
+        // Only the contract itself can update members
+        let err = execute(deps.as_mut(), mock_env(), info, update.clone()).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        // Zero weight voters are rejected
+        let info = mock_info(MOCK_CONTRACT_ADDR, &[]);
+        let zero_weight = ExecuteMsg::UpdateMembers {
+            add: vec![voter(SOMEBODY, 0)],
+            remove: vec![],
+        };
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), zero_weight).unwrap_err();
+        assert_eq!(err, ContractError::VoterZeroWeight {});
+
+        // Valid update works: 1 + 7 + 2 + 3 + 4 + 2
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), update).unwrap();
+        assert_eq!(
+            res,
+            Response::new()
+                .add_attribute("action", "update_members")
+                .add_attribute("sender", MOCK_CONTRACT_ADDR)
+                .add_attribute("total_weight", 19.to_string())
+        );
+
+        // Verify
+        let threshold: ThresholdResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Threshold {}).unwrap())
+                .unwrap();
+        assert_eq!(
+            threshold,
+            ThresholdResponse::AbsoluteCount {
+                weight: 3,
+                total_weight: 19
+            }
+        );
+        let removed = query_voter(deps.as_ref(), VOTER5.into()).unwrap();
+        assert_eq!(removed.weight, None);
+        let reweighted = query_voter(deps.as_ref(), VOTER1.into()).unwrap();
+        assert_eq!(reweighted.weight, Some(7));
+        let added = query_voter(deps.as_ref(), SOMEBODY.into()).unwrap();
+        assert_eq!(added.weight, Some(2));
+
+        // Removing so much weight that the threshold cannot be reached fails
+        let too_few = ExecuteMsg::UpdateMembers {
+            add: vec![],
+            remove: vec![
+                VOTER1.into(),
+                VOTER2.into(),
+                VOTER3.into(),
+                VOTER4.into(),
+                SOMEBODY.into(),
+            ],
+        };
+        let err = execute(deps.as_mut(), mock_env(), info, too_few).unwrap_err();
+        assert_eq!(err, ContractError::UnreachableWeight {});
+    }
+
+    #[test]
+    fn test_voters_are_taken_at_proposal_start() {
+        let mut deps = mock_dependencies(&[]);
+
+        let info = mock_info(OWNER, &[]);
+        setup_test_case(deps.as_mut(), info.clone(), 3, Duration::Time(2000000)).unwrap();
+        let proposal = ExecuteMsg::Propose {
+            title: "Pay somebody".to_string(),
+            description: "Do I pay her?".to_string(),
+            msgs: vec![],
+            earliest: None,
+            latest: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, proposal.clone()).unwrap();
+        let proposal_id: u64 = res.attributes[2].value.parse().unwrap();
+
+        // Change the members after the proposal was created
+        let update = ExecuteMsg::UpdateMembers {
+            add: vec![voter(SOMEBODY, 2), voter(VOTER1, 7)],
+            remove: vec![VOTER5.into()],
+        };
+        let env = mock_env_height(1);
+        let info = mock_info(MOCK_CONTRACT_ADDR, &[]);
+        execute(deps.as_mut(), env.clone(), info, update).unwrap();
+        let added = query_voter(deps.as_ref(), SOMEBODY.into()).unwrap();
+        assert_eq!(added.weight, Some(2));
+
+        // Proposals created in the same block still use the total weight at its start,
+        // like the voter weights, later ones the new total weight
+        let info = mock_info(OWNER, &[]);
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), proposal.clone()).unwrap();
+        let same_block_id: u64 = res.attributes[2].value.parse().unwrap();
+        let res = execute(deps.as_mut(), mock_env_height(2), info, proposal).unwrap();
+        let next_block_id: u64 = res.attributes[2].value.parse().unwrap();
+        let original = query_proposal(deps.as_ref(), env.clone(), proposal_id).unwrap();
+        let same_block = query_proposal(deps.as_ref(), env.clone(), same_block_id).unwrap();
+        assert_eq!(same_block.threshold, original.threshold);
+        let next_block = query_proposal(deps.as_ref(), env, next_block_id).unwrap();
+        assert_eq!(
+            next_block.threshold,
+            query_threshold(deps.as_ref()).unwrap()
+        );
+        assert_ne!(next_block.threshold, original.threshold);
+
+        // New members cannot vote on it
+        let yes_vote = ExecuteMsg::Vote {
+            proposal_id,
+            vote: Vote::Yes,
+        };
+        let env = mock_env_height(2);
+        let info = mock_info(SOMEBODY, &[]);
+        let err = execute(deps.as_mut(), env.clone(), info, yes_vote.clone()).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        // Existing members vote with their weight at the start
+        let info = mock_info(VOTER1, &[]);
+        let res = execute(deps.as_mut(), env.clone(), info, yes_vote.clone()).unwrap();
+        assert_eq!(res.attributes[3].value, "Open");
+        assert_eq!(get_tally(deps.as_ref(), proposal_id), 2);
+
+        // Removed members can still vote on it
+        let info = mock_info(VOTER5, &[]);
+        let res = execute(deps.as_mut(), env, info, yes_vote).unwrap();
+        assert_eq!(res.attributes[3].value, "Passed");
+    }
+
+    #[test]
+    fn test_update_config_works() {
+        let mut deps = mock_dependencies(&[]);
+
+        let threshold_weight = 3;
+        let voting_period = Duration::Time(2000000);
+
+        let info = mock_info(OWNER, &[]);
+        setup_test_case(deps.as_mut(), info.clone(), threshold_weight, voting_period).unwrap();
+
+        let update = ExecuteMsg::UpdateConfig(ConfigUpdate {
+            threshold: Some(Threshold::AbsoluteCount { weight: 10 }),
+            max_voting_period: Some(Duration::Height(1000)),
+            ..Default::default()
+        });
+
+        // Only the contract itself can update the config
+        let err = execute(deps.as_mut(), mock_env(), info, update.clone()).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        // Zero required weight fails
+        let info = mock_info(MOCK_CONTRACT_ADDR, &[]);
+        let zero_weight = ExecuteMsg::UpdateConfig(ConfigUpdate {
+            threshold: Some(Threshold::AbsoluteCount { weight: 0 }),
+            ..Default::default()
+        });
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), zero_weight).unwrap_err();
+        assert_eq!(err, ContractError::ZeroWeight {});
+
+        // Total weight less than required weight not allowed
+        let unreachable = ExecuteMsg::UpdateConfig(ConfigUpdate {
+            threshold: Some(Threshold::AbsoluteCount { weight: 17 }),
+            ..Default::default()
+        });
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), unreachable).unwrap_err();
+        assert_eq!(err, ContractError::UnreachableWeight {});
+
+        // Valid update works
+        let res = execute(deps.as_mut(), mock_env(), info, update).unwrap();
+        assert_eq!(
+            res,
+            Response::new()
+                .add_attribute("action", "update_config")
+                .add_attribute("sender", MOCK_CONTRACT_ADDR)
+                .add_attribute("max_voting_period", "height: 1000")
+        );
+
+        // Verify
+        let threshold = query_threshold(deps.as_ref()).unwrap();
+        assert_eq!(
+            threshold,
+            ThresholdResponse::AbsoluteCount {
+                weight: 10,
+                total_weight: 16
+            }
+        );
+        let cfg = CONFIG.load(deps.as_ref().storage).unwrap();
+        assert_eq!(cfg.max_voting_period, Duration::Height(1000));
+    }
+
+    #[test]
+    fn test_update_config_keeps_omitted_fields() {
+        let mut deps = mock_dependencies(&[]);
+
+        let info = mock_info(OWNER, &[]);
+        setup_test_case(deps.as_mut(), info, 3, Duration::Time(2000000)).unwrap();
+        let info = mock_info(MOCK_CONTRACT_ADDR, &[]);
+        let update = ExecuteMsg::UpdateConfig(ConfigUpdate {
+            execution_delay: Some(Some(Duration::Time(100))),
+            ..Default::default()
+        });
+        execute(deps.as_mut(), mock_env(), info.clone(), update).unwrap();
+
+        // Only the given field changes
+        let update: ExecuteMsg<CustomMsg> =
+            from_slice(br#"{"update_config":{"track_failures":true}}"#).unwrap();
+        execute(deps.as_mut(), mock_env(), info.clone(), update).unwrap();
+        let cfg = CONFIG.load(deps.as_ref().storage).unwrap();
+        assert!(cfg.track_failures);
+        assert_eq!(cfg.execution_delay, Some(Duration::Time(100)));
+        assert_eq!(cfg.threshold, Threshold::AbsoluteCount { weight: 3 });
+        assert_eq!(cfg.max_voting_period, Duration::Time(2000000));
+
+        // An explicit null removes an optional setting
+        let update: ExecuteMsg<CustomMsg> =
+            from_slice(br#"{"update_config":{"execution_delay":null}}"#).unwrap();
+        execute(deps.as_mut(), mock_env(), info, update).unwrap();
+        let cfg = CONFIG.load(deps.as_ref().storage).unwrap();
+        assert_eq!(cfg.execution_delay, None);
+        assert!(cfg.track_failures);
+    }
+
+    #[test]
+    fn test_proposal_keeps_threshold_snapshot() {
+        let mut deps = mock_dependencies(&[]);
+
+        let threshold_weight = 5;
+        let voting_period = Duration::Time(2000000);
+
+        let info = mock_info(OWNER, &[]);
+        setup_test_case(deps.as_mut(), info.clone(), threshold_weight, voting_period).unwrap();
+
+        // Propose with the owner's weight of 1 in favour
+        let proposal = ExecuteMsg::Propose {
+            title: "Pay somebody".to_string(),
+            description: "Do I pay her?".to_string(),
+            msgs: vec![],
+            earliest: None,
+            latest: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, proposal).unwrap();
+        let proposal_id: u64 = res.attributes[2].value.parse().unwrap();
+
+        // Lower the threshold below the current yes weight
+        let update = ExecuteMsg::UpdateConfig(ConfigUpdate {
+            threshold: Some(Threshold::AbsoluteCount { weight: 1 }),
+            ..Default::default()
+        });
+        let info = mock_info(MOCK_CONTRACT_ADDR, &[]);
+        execute(deps.as_mut(), mock_env(), info, update).unwrap();
+
+        // The existing proposal is still judged by the old threshold
+        let prop = query_proposal(deps.as_ref(), mock_env(), proposal_id).unwrap();
+        assert_eq!(prop.status, Status::Open);
+        assert_eq!(
+            prop.threshold,
+            ThresholdResponse::AbsoluteCount {
+                weight: 5,
+                total_weight: 16
+            }
+        );
+
+        // And still needs enough yes votes of its own threshold
+        let info = mock_info(VOTER1, &[]);
+        let vote = ExecuteMsg::Vote {
+            proposal_id,
+            vote: Vote::Yes,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, vote).unwrap();
+        assert_eq!(res.attributes[3].value, "Open");
+    }
+
+    #[test]
+    fn test_query_votes_works() {
+        let mut deps = mock_dependencies(&[]);
+
+        let threshold_weight = 10;
+        let voting_period = Duration::Time(2000000);
+
+        let info = mock_info(OWNER, &[]);
+        setup_test_case(deps.as_mut(), info.clone(), threshold_weight, voting_period).unwrap();
+
+        let proposal = ExecuteMsg::Propose {
+            title: "Pay somebody".to_string(),
+            description: "Do I pay her?".to_string(),
+            msgs: vec![],
+            earliest: None,
+            latest: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, proposal).unwrap();
+        let proposal_id: u64 = res.attributes[2].value.parse().unwrap();
+
+        // Every kind of ballot is recorded
+        for (addr, vote) in [
+            (VOTER2, Vote::No),
+            (VOTER3, Vote::Abstain),
+            (VOTER4, Vote::Veto),
+        ] {
+            let msg = ExecuteMsg::Vote { proposal_id, vote };
+            execute(deps.as_mut(), mock_env(), mock_info(addr, &[]), msg).unwrap();
+        }
+
+        // Query a single vote
+        let res = query_vote(deps.as_ref(), proposal_id, VOTER4.into()).unwrap();
+        assert_eq!(
+            res.vote,
+            Some(VoteInfo {
+                voter: VOTER4.into(),
+                vote: Vote::Veto,
+                weight: 4
+            })
+        );
+        let res = query_vote(deps.as_ref(), proposal_id, VOTER1.into()).unwrap();
+        assert_eq!(res.vote, None);
+
+        // List all votes, paginated by address
+        let res = list_votes(deps.as_ref(), proposal_id, None, Some(2)).unwrap();
+        assert_eq!(
+            res.votes,
+            vec![
+                VoteInfo {
+                    voter: OWNER.into(),
+                    vote: Vote::Yes,
+                    weight: 1
+                },
+                VoteInfo {
+                    voter: VOTER2.into(),
+                    vote: Vote::No,
+                    weight: 2
+                },
+            ]
+        );
+        let res = list_votes(deps.as_ref(), proposal_id, Some(VOTER2.into()), None).unwrap();
+        assert_eq!(
+            res.votes,
+            vec![
+                VoteInfo {
+                    voter: VOTER3.into(),
+                    vote: Vote::Abstain,
+                    weight: 3
+                },
+                VoteInfo {
+                    voter: VOTER4.into(),
+                    vote: Vote::Veto,
+                    weight: 4
+                },
+            ]
+        );
+
+        // Only yes votes count towards the tally
+        assert_eq!(get_tally(deps.as_ref(), proposal_id), 1);
+    }
+
+    #[test]
+    fn test_change_vote_works() {
+        let mut deps = mock_dependencies(&[]);
+
+        let threshold_weight = 8;
+        let voting_period = Duration::Time(2000000);
+
+        let info = mock_info(OWNER, &[]);
+        setup_test_case(deps.as_mut(), info, threshold_weight, voting_period).unwrap();
+
+        let proposal = ExecuteMsg::Propose {
+            title: "Pay somebody".to_string(),
+            description: "Do I pay her?".to_string(),
+            msgs: vec![],
+            earliest: None,
+            latest: None,
+        };
+        let info = mock_info(SOMEBODY, &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, proposal).unwrap();
+        let proposal_id: u64 = res.attributes[2].value.parse().unwrap();
+
+        let yes_vote = ExecuteMsg::Vote {
+            proposal_id,
+            vote: Vote::Yes,
+        };
+        let no_vote = ExecuteMsg::Vote {
+            proposal_id,
+            vote: Vote::No,
+        };
+        let retract = ExecuteMsg::Retract { proposal_id };
+
+        // Retracting without a vote fails
+        let info = mock_info(VOTER4, &[]);
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), retract.clone()).unwrap_err();
+        assert_eq!(err, ContractError::NotVoted {});
+
+        // Vote yes, then change to no
+        execute(deps.as_mut(), mock_env(), info.clone(), yes_vote.clone()).unwrap();
+        assert_eq!(get_tally(deps.as_ref(), proposal_id), 4);
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), no_vote).unwrap();
+        assert_eq!(
+            res,
+            Response::new()
+                .add_attribute("action", "change_vote")
+                .add_attribute("sender", VOTER4)
+                .add_attribute("proposal_id", proposal_id.to_string())
+                .add_attribute("status", "Open")
+        );
+        assert_eq!(get_tally(deps.as_ref(), proposal_id), 0);
+
+        // Change back to yes, then retract
+        execute(deps.as_mut(), mock_env(), info.clone(), yes_vote.clone()).unwrap();
+        let prop = PROPOSALS.load(&deps.storage, proposal_id.into()).unwrap();
+        assert_eq!(prop.yes_weight, 4);
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), retract.clone()).unwrap();
+        assert_eq!(
+            res,
+            Response::new()
+                .add_attribute("action", "retract")
+                .add_attribute("sender", VOTER4)
+                .add_attribute("proposal_id", proposal_id.to_string())
+                .add_attribute("status", "Open")
+        );
+        let prop = PROPOSALS.load(&deps.storage, proposal_id.into()).unwrap();
+        assert_eq!(prop.yes_weight, 0);
+        let res = query_vote(deps.as_ref(), proposal_id, VOTER4.into()).unwrap();
+        assert_eq!(res.vote, None);
+
+        // Voting again after retracting is a new vote
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), yes_vote.clone()).unwrap();
+        assert_eq!(res.attributes[0].value, "vote");
+
+        // Once passed, votes can no longer be changed or retracted
+        let res = execute(deps.as_mut(), mock_env(), mock_info(VOTER5, &[]), yes_vote).unwrap();
+        assert_eq!(res.attributes[3].value, "Passed");
+        let err = execute(deps.as_mut(), mock_env(), info, retract).unwrap_err();
+        assert_eq!(err, ContractError::NotOpen {});
+    }
+
+    #[test]
+    fn test_absolute_percentage_works() {
+        let mut deps = mock_dependencies(&[]);
+
+        let info = mock_info(OWNER, &[]);
+        let instantiate_msg = init_msg(
+            vec![
+                voter(OWNER, 1),
+                voter(VOTER1, 2),
+                voter(VOTER2, 3),
+                voter(VOTER3, 4),
+            ],
+            Threshold::AbsolutePercentage {
+                percentage: Decimal::percent(51),
+            },
+            Duration::Time(2000000),
+        );
+
+        // Percentages over 100% are not reachable
+        let mut invalid = instantiate_msg.clone();
+        invalid.threshold = Threshold::AbsolutePercentage {
+            percentage: Decimal::percent(101),
+        };
+        let err = instantiate(deps.as_mut(), mock_env(), info.clone(), invalid).unwrap_err();
+        assert_eq!(err, ContractError::UnreachableWeight {});
+
+        instantiate(deps.as_mut(), mock_env(), info.clone(), instantiate_msg).unwrap();
+        let threshold = query_threshold(deps.as_ref()).unwrap();
+        assert_eq!(
+            threshold,
+            ThresholdResponse::AbsolutePercentage {
+                percentage: Decimal::percent(51),
+                total_weight: 10
+            }
+        );
+
+        // 51% of 10 requires a weight of 6
+        let proposal = ExecuteMsg::Propose {
+            title: "Pay somebody".to_string(),
+            description: "Do I pay her?".to_string(),
+            msgs: vec![],
+            earliest: None,
+            latest: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, proposal).unwrap();
+        let proposal_id: u64 = res.attributes[2].value.parse().unwrap();
+        let yes_vote = ExecuteMsg::Vote {
+            proposal_id,
+            vote: Vote::Yes,
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(VOTER1, &[]),
+            yes_vote.clone(),
+        )
+        .unwrap();
+        assert_eq!(res.attributes[3].value, "Open");
+
+        // Adding a member does not change the total weight of the existing proposal
+        let update = ExecuteMsg::UpdateMembers {
+            add: vec![voter(VOTER4, 10)],
+            remove: vec![],
+        };
+        let info = mock_info(MOCK_CONTRACT_ADDR, &[]);
+        execute(deps.as_mut(), mock_env(), info, update).unwrap();
+
+        let res = execute(deps.as_mut(), mock_env(), mock_info(VOTER2, &[]), yes_vote).unwrap();
+        assert_eq!(res.attributes[3].value, "Passed");
+        let prop = query_proposal(deps.as_ref(), mock_env(), proposal_id).unwrap();
+        assert_eq!(
+            prop.threshold,
+            ThresholdResponse::AbsolutePercentage {
+                percentage: Decimal::percent(51),
+                total_weight: 10
+            }
+        );
+    }
+
+    #[test]
+    fn test_threshold_quorum_works() {
+        let mut deps = mock_dependencies(&[]);
+
+        let info = mock_info(OWNER, &[]);
+        let voting_period = Duration::Time(2000000);
+        let instantiate_msg = init_msg(
+            vec![
+                voter(OWNER, 1),
+                voter(VOTER1, 1),
+                voter(VOTER2, 2),
+                voter(VOTER3, 3),
+                voter(VOTER4, 4),
+                voter(VOTER5, 5),
+            ],
+            Threshold::ThresholdQuorum {
+                threshold: Decimal::percent(60),
+                quorum: Decimal::percent(50),
+            },
+            voting_period,
+        );
+        instantiate(deps.as_mut(), mock_env(), info.clone(), instantiate_msg).unwrap();
+        let threshold = query_threshold(deps.as_ref()).unwrap();
+        assert_eq!(
+            threshold,
+            ThresholdResponse::ThresholdQuorum {
+                threshold: Decimal::percent(60),
+                quorum: Decimal::percent(50),
+                total_weight: 16
+            }
+        );
+
+        let proposal = ExecuteMsg::Propose {
+            title: "Pay somebody".to_string(),
+            description: "Do I pay her?".to_string(),
+            msgs: vec![],
+            earliest: None,
+            latest: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), proposal.clone()).unwrap();
+        let early_id: u64 = res.attributes[2].value.parse().unwrap();
+        let res = execute(deps.as_mut(), mock_env(), info, proposal).unwrap();
+        let late_id: u64 = res.attributes[2].value.parse().unwrap();
+
+        let vote = |deps: DepsMut, addr: &str, proposal_id: u64, vote: Vote| {
+            let msg = ExecuteMsg::Vote { proposal_id, vote };
+            execute(deps, mock_env(), mock_info(addr, &[]), msg).unwrap()
+        };
+
+        // Abstaining counts towards quorum but not the threshold:
+        // 1 yes + 3 abstain + 4 yes = 8 of 16 reaches the quorum,
+        // but 5 yes out of 13 possible opinions is not yet 60%
+        vote(deps.as_mut(), VOTER3, early_id, Vote::Abstain);
+        let res = vote(deps.as_mut(), VOTER4, early_id, Vote::Yes);
+        assert_eq!(res.attributes[3].value, "Open");
+        // 10 yes out of 13 possible opinions passes early
+        let res = vote(deps.as_mut(), VOTER5, early_id, Vote::Yes);
+        assert_eq!(res.attributes[3].value, "Passed");
+
+        // 1 yes + 1 yes + 2 no = 4 of 16 does not reach quorum
+        vote(deps.as_mut(), VOTER1, late_id, Vote::Yes);
+        vote(deps.as_mut(), VOTER2, late_id, Vote::No);
+        // 5 more yes reach quorum with 7 yes out of 9 opinions cast,
+        // but it cannot pass early as 7 of 16 possible opinions is not enough
+        let res = vote(deps.as_mut(), VOTER5, late_id, Vote::Yes);
+        assert_eq!(res.attributes[3].value, "Open");
+        let execution = ExecuteMsg::Execute {
+            proposal_id: late_id,
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(SOMEBODY, &[]),
+            execution.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::WrongExecuteStatus {});
+
+        // Once the voting period is over it has passed and cannot be closed
+        let env = mock_env_time(2000001);
+        let prop = query_proposal(deps.as_ref(), env.clone(), late_id).unwrap();
+        assert_eq!(prop.status, Status::Passed);
+        let closing = ExecuteMsg::Close {
+            proposal_id: late_id,
+        };
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info(SOMEBODY, &[]),
+            closing,
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::WrongCloseStatus {});
+        execute(deps.as_mut(), env, mock_info(SOMEBODY, &[]), execution).unwrap();
+    }
+
+    #[test]
+    fn test_unreachable_threshold_rejects_early() {
+        let mut deps = mock_dependencies(&[]);
+
+        let threshold_weight = 10;
+        let voting_period = Duration::Time(2000000);
+
+        let info = mock_info(OWNER, &[]);
+        setup_test_case(deps.as_mut(), info.clone(), threshold_weight, voting_period).unwrap();
+
+        let proposal = ExecuteMsg::Propose {
+            title: "Pay somebody".to_string(),
+            description: "Do I pay her?".to_string(),
+            msgs: vec![],
+            earliest: None,
+            latest: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, proposal).unwrap();
+        let proposal_id: u64 = res.attributes[2].value.parse().unwrap();
+
+        // 1 yes, 5 no: 10 weight could still vote yes
+        let no_vote = ExecuteMsg::Vote {
+            proposal_id,
+            vote: Vote::No,
+        };
+        let info = mock_info(VOTER5, &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, no_vote.clone()).unwrap();
+        assert_eq!(res.attributes[3].value, "Open");
+
+        // 1 yes, 5 no, 1 veto: at most 1 + 9 yes is still enough
+        let veto_vote = ExecuteMsg::Vote {
+            proposal_id,
+            vote: Vote::Veto,
+        };
+        let info = mock_info(VOTER1, &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, veto_vote).unwrap();
+        assert_eq!(res.attributes[3].value, "Open");
+
+        // 1 yes, 5 no, 1 veto, 2 no: at most 1 + 7 yes can no longer pass
+        let info = mock_info(VOTER2, &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, no_vote).unwrap();
+        assert_eq!(res.attributes[3].value, "Rejected");
+        let prop = query_proposal(deps.as_ref(), mock_env(), proposal_id).unwrap();
+        assert_eq!(prop.status, Status::Rejected);
+
+        // No more votes are accepted
+        let yes_vote = ExecuteMsg::Vote {
+            proposal_id,
+            vote: Vote::Yes,
+        };
+        let info = mock_info(VOTER4, &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, yes_vote).unwrap_err();
+        assert_eq!(err, ContractError::NotOpen {});
+    }
+
+    #[test]
+    fn test_pending_proposal_works() {
+        let mut deps = mock_dependencies(&[]);
+
+        let threshold_weight = 4;
+        let voting_period = Duration::Height(2000000);
+
+        let info = mock_info(OWNER, &[]);
+        setup_test_case(deps.as_mut(), info, threshold_weight, voting_period).unwrap();
+
+        // Voting cannot start after it ends
+        let env = mock_env();
+        let info = mock_info(VOTER4, &[]);
+        let proposal_wrong_start = ExecuteMsg::Propose {
+            title: "Pay somebody".to_string(),
+            description: "Do I pay her?".to_string(),
+            msgs: vec![],
+            earliest: Some(Expiration::AtHeight(env.block.height + 100)),
+            latest: Some(Expiration::AtHeight(env.block.height + 100)),
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            proposal_wrong_start,
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::WrongExpiration {});
+
+        // A proposal with a later start is pending, even with enough vote power to pass
+        let proposal = ExecuteMsg::Propose {
+            title: "Pay somebody".to_string(),
+            description: "Do I pay her?".to_string(),
+            msgs: vec![],
+            earliest: Some(Expiration::AtHeight(env.block.height + 100)),
+            latest: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, proposal).unwrap();
+        assert_eq!(res.attributes[3].value, "Pending");
+        let proposal_id: u64 = res.attributes[2].value.parse().unwrap();
+
+        // Votes are not accepted before the start
+        let yes_vote = ExecuteMsg::Vote {
+            proposal_id,
+            vote: Vote::Yes,
+        };
+        let info = mock_info(VOTER1, &[]);
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), yes_vote.clone()).unwrap_err();
+        assert_eq!(err, ContractError::NotStarted {});
+        let prop = query_proposal(deps.as_ref(), mock_env(), proposal_id).unwrap();
+        assert_eq!(prop.status, Status::Pending);
+
+        // Once started, the proposer's vote counts
+        let env = mock_env_height(100);
+        let prop = query_proposal(deps.as_ref(), env.clone(), proposal_id).unwrap();
+        assert_eq!(prop.status, Status::Passed);
+
+        // So it is no longer open for votes or retractions
+        let err = execute(deps.as_mut(), env.clone(), info.clone(), yes_vote).unwrap_err();
+        assert_eq!(err, ContractError::NotOpen {});
+        let retraction = ExecuteMsg::Retract { proposal_id };
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info(VOTER4, &[]),
+            retraction,
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::NotOpen {});
+        let prop = query_proposal(deps.as_ref(), env.clone(), proposal_id).unwrap();
+        assert_eq!(prop.status, Status::Passed);
+
+        // With a higher threshold, voting opens at the start
+        let update = ExecuteMsg::UpdateConfig(ConfigUpdate {
+            threshold: Some(Threshold::AbsoluteCount { weight: 6 }),
+            ..Default::default()
+        });
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(MOCK_CONTRACT_ADDR, &[]),
+            update,
+        )
+        .unwrap();
+        let proposal = ExecuteMsg::Propose {
+            title: "Pay somebody".to_string(),
+            description: "Do I pay her?".to_string(),
+            msgs: vec![],
+            earliest: Some(Expiration::AtHeight(env.block.height + 100)),
+            latest: None,
+        };
+        let res = execute(deps.as_mut(), env.clone(), mock_info(VOTER4, &[]), proposal).unwrap();
+        let proposal_id: u64 = res.attributes[2].value.parse().unwrap();
+        let env = mock_env_height(200);
+        let prop = query_proposal(deps.as_ref(), env.clone(), proposal_id).unwrap();
+        assert_eq!(prop.status, Status::Open);
+
+        let yes_vote = ExecuteMsg::Vote {
+            proposal_id,
+            vote: Vote::Yes,
+        };
+        let res = execute(deps.as_mut(), env, info, yes_vote).unwrap();
+        assert_eq!(res.attributes[3].value, "Open");
+    }
+
+    #[test]
+    fn test_execution_delay_works() {
+        let mut deps = mock_dependencies(&[]);
+
+        let info = mock_info(OWNER, &[]);
+        let voting_period = Duration::Height(2000000);
+        let mut instantiate_msg = InstantiateMsg {
+            execution_delay: Some(Duration::Time(100)),
+            ..init_msg(
+                vec![voter(OWNER, 1), voter(VOTER1, 2), voter(VOTER2, 3)],
+                Threshold::AbsoluteCount { weight: 3 },
+                voting_period,
+            )
+        };
+
+        // The delay must be in the same unit as the voting period
+        let err = instantiate(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            instantiate_msg.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::WrongExecutionPeriod {});
+        instantiate_msg.execution_delay = Some(Duration::Height(10));
+        instantiate(deps.as_mut(), mock_env(), info.clone(), instantiate_msg).unwrap();
+
+        let proposal = ExecuteMsg::Propose {
+            title: "Pay somebody".to_string(),
+            description: "Do I pay her?".to_string(),
+            msgs: vec![],
+            earliest: None,
+            latest: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, proposal).unwrap();
+        let proposal_id: u64 = res.attributes[2].value.parse().unwrap();
+
+        // Passes at a later height
+        let yes_vote = ExecuteMsg::Vote {
+            proposal_id,
+            vote: Vote::Yes,
+        };
+        let info = mock_info(VOTER1, &[]);
+        let res = execute(deps.as_mut(), mock_env_height(5), info, yes_vote).unwrap();
+        assert_eq!(res.attributes[3].value, "Passed");
+        let prop = PROPOSALS.load(&deps.storage, proposal_id.into()).unwrap();
+        let passed_at = mock_env_height(5).block;
+        assert_eq!(
+            prop.passed_at,
+            Some(PassedAt {
+                height: passed_at.height,
+                time: passed_at.time
+            })
+        );
+
+        // Cannot be executed during the delay
+        let execution = ExecuteMsg::Execute { proposal_id };
+        let info = mock_info(SOMEBODY, &[]);
+        let err = execute(
+            deps.as_mut(),
+            mock_env_height(14),
+            info.clone(),
+            execution.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Timelocked {});
+
+        // But right after it
+        execute(deps.as_mut(), mock_env_height(15), info, execution).unwrap();
+    }
+
+    #[test]
+    fn test_execution_delay_counts_from_start_of_pending_proposal() {
+        let mut deps = mock_dependencies(&[]);
+
+        let info = mock_info(OWNER, &[]);
+        let instantiate_msg = InstantiateMsg {
+            execution_delay: Some(Duration::Height(10)),
+            ..init_msg(
+                vec![voter(OWNER, 1), voter(VOTER1, 2), voter(VOTER2, 3)],
+                Threshold::AbsoluteCount { weight: 3 },
+                Duration::Height(2000000),
+            )
+        };
+        instantiate(deps.as_mut(), mock_env(), info, instantiate_msg).unwrap();
+
+        // The proposer's vote is enough, so it passes as soon as voting starts
+        let start = mock_env_height(10).block.height;
+        let proposal = ExecuteMsg::Propose {
+            title: "Pay somebody".to_string(),
+            description: "Do I pay her?".to_string(),
+            msgs: vec![],
+            earliest: Some(Expiration::AtHeight(start)),
+            latest: None,
+        };
+        let info = mock_info(VOTER2, &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, proposal).unwrap();
+        let proposal_id: u64 = res.attributes[2].value.parse().unwrap();
+        let prop = query_proposal(deps.as_ref(), mock_env_height(10), proposal_id).unwrap();
+        assert_eq!(prop.status, Status::Passed);
+
+        // The delay counts from the start of voting
+        let execution = ExecuteMsg::Execute { proposal_id };
+        let info = mock_info(SOMEBODY, &[]);
+        let env = mock_env_height(19);
+        let err = execute(deps.as_mut(), env, info.clone(), execution.clone()).unwrap_err();
+        assert_eq!(err, ContractError::Timelocked {});
+        let res = execute(deps.as_mut(), mock_env_height(20), info, execution).unwrap();
+        assert_eq!(res.attributes[0].value, "execute");
+    }
+
+    #[test]
+    fn test_execution_delay_counts_from_expiry_with_quorum() {
+        let mut deps = mock_dependencies(&[]);
+
+        let info = mock_info(OWNER, &[]);
+        let instantiate_msg = InstantiateMsg {
+            execution_delay: Some(Duration::Height(5)),
+            ..init_msg(
+                vec![voter(OWNER, 1), voter(VOTER1, 2), voter(VOTER2, 3)],
+                Threshold::ThresholdQuorum {
+                    threshold: Decimal::percent(50),
+                    quorum: Decimal::percent(50),
+                },
+                Duration::Height(10),
+            )
+        };
+        instantiate(deps.as_mut(), mock_env(), info.clone(), instantiate_msg).unwrap();
+
+        let proposal = ExecuteMsg::Propose {
+            title: "Pay somebody".to_string(),
+            description: "Do I pay her?".to_string(),
+            msgs: vec![],
+            earliest: None,
+            latest: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, proposal).unwrap();
+        let proposal_id: u64 = res.attributes[2].value.parse().unwrap();
+
+        // With the missing weight counted against it, it only passes once voting is over
+        let abstain = ExecuteMsg::Vote {
+            proposal_id,
+            vote: Vote::Abstain,
+        };
+        let info = mock_info(VOTER1, &[]);
+        let res = execute(deps.as_mut(), mock_env_height(1), info, abstain).unwrap();
+        assert_eq!(res.attributes[3].value, "Open");
+        let prop = query_proposal(deps.as_ref(), mock_env_height(10), proposal_id).unwrap();
+        assert_eq!(prop.status, Status::Passed);
+
+        // The delay counts from the end of voting
+        let execution = ExecuteMsg::Execute { proposal_id };
+        let info = mock_info(SOMEBODY, &[]);
+        let env = mock_env_height(14);
+        let err = execute(deps.as_mut(), env, info.clone(), execution.clone()).unwrap_err();
+        assert_eq!(err, ContractError::Timelocked {});
+        let res = execute(deps.as_mut(), mock_env_height(15), info, execution).unwrap();
+        assert_eq!(res.attributes[0].value, "execute");
+    }
+
+    #[test]
+    fn test_execution_window_works() {
+        let mut deps = mock_dependencies(&[]);
+
+        let info = mock_info(OWNER, &[]);
+        let instantiate_msg = InstantiateMsg {
+            execution_delay: Some(Duration::Time(100)),
+            execution_window: Some(Duration::Time(1000)),
+            ..init_msg(
+                vec![voter(OWNER, 1), voter(VOTER1, 2), voter(VOTER2, 3)],
+                Threshold::AbsoluteCount { weight: 3 },
+                Duration::Time(2000000),
+            )
+        };
+        instantiate(deps.as_mut(), mock_env(), info.clone(), instantiate_msg).unwrap();
+
+        // Proposals from VOTER2 pass right away
+        let proposal = ExecuteMsg::Propose {
+            title: "Pay somebody".to_string(),
+            description: "Do I pay her?".to_string(),
+            msgs: vec![],
+            earliest: None,
+            latest: None,
+        };
+        let info = mock_info(VOTER2, &[]);
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), proposal.clone()).unwrap();
+        let in_time_id: u64 = res.attributes[2].value.parse().unwrap();
+        let res = execute(deps.as_mut(), mock_env(), info, proposal).unwrap();
+        let too_late_id: u64 = res.attributes[2].value.parse().unwrap();
+
+        // Can be executed up until the end of the window following the delay
+        let info = mock_info(SOMEBODY, &[]);
+        let execution = ExecuteMsg::Execute {
+            proposal_id: in_time_id,
+        };
+        execute(deps.as_mut(), mock_env_time(1099), info.clone(), execution).unwrap();
+
+        // But no longer afterwards
+        let env = mock_env_time(1100);
+        let status = query_proposal_status(deps.as_ref(), env.clone(), too_late_id).unwrap();
+        assert_eq!(status.status, Status::ExecutionExpired);
+        // which cw3 clients see as rejected, as it can only be closed
+        let prop = query_proposal(deps.as_ref(), env.clone(), too_late_id).unwrap();
+        assert_eq!(prop.status, Status::Rejected);
+        let execution = ExecuteMsg::Execute {
+            proposal_id: too_late_id,
+        };
+        let err = execute(deps.as_mut(), env, info, execution).unwrap_err();
+        assert_eq!(err, ContractError::ExecutionExpired {});
+    }
+
+    #[test]
+    fn test_executor_policy_works() {
+        let mut deps = mock_dependencies(&[]);
+
+        let threshold_weight = 3;
+        let voting_period = Duration::Time(2000000);
+
+        let info = mock_info(OWNER, &[]);
+        setup_test_case(deps.as_mut(), info, threshold_weight, voting_period).unwrap();
+
+        // Proposals from VOTER3 pass right away
+        let proposal = ExecuteMsg::Propose {
+            title: "Pay somebody".to_string(),
+            description: "Do I pay her?".to_string(),
+            msgs: vec![],
+            earliest: None,
+            latest: None,
+        };
+        let info = mock_info(VOTER3, &[]);
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), proposal.clone()).unwrap();
+        let member_id: u64 = res.attributes[2].value.parse().unwrap();
+        let res = execute(deps.as_mut(), mock_env(), info, proposal).unwrap();
+        let proposer_id: u64 = res.attributes[2].value.parse().unwrap();
+
+        let set_executor = |deps: DepsMut, executor: Executor| {
+            let update = ExecuteMsg::UpdateConfig(ConfigUpdate {
+                executor: Some(executor),
+                ..Default::default()
+            });
+            let info = mock_info(MOCK_CONTRACT_ADDR, &[]);
+            execute(deps, mock_env(), info, update).unwrap();
+        };
+
+        // Only members can execute
+        set_executor(deps.as_mut(), Executor::Member {});
+        let execution = ExecuteMsg::Execute {
+            proposal_id: member_id,
+        };
+        let info = mock_info(SOMEBODY, &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, execution.clone()).unwrap_err();
+        assert_eq!(err, ContractError::UnauthorizedExecutor {});
+        let info = mock_info(VOTER1, &[]);
+        execute(deps.as_mut(), mock_env(), info, execution).unwrap();
+
+        // Only the proposer can execute
+        set_executor(deps.as_mut(), Executor::Proposer {});
+        let execution = ExecuteMsg::Execute {
+            proposal_id: proposer_id,
+        };
+        let info = mock_info(VOTER1, &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, execution.clone()).unwrap_err();
+        assert_eq!(err, ContractError::UnauthorizedExecutor {});
+        let info = mock_info(VOTER3, &[]);
+        execute(deps.as_mut(), mock_env(), info, execution).unwrap();
+    }
+
+    #[test]
+    fn test_proposer_policy_works() {
+        let mut deps = mock_dependencies(&[]);
+
+        let info = mock_info(OWNER, &[]);
+        let voting_period = Duration::Time(2000000);
+        let mut instantiate_msg = InstantiateMsg {
+            proposer: Proposer::Allowlist {},
+            ..init_msg(
+                vec![voter(OWNER, 1), voter(VOTER1, 2), voter(VOTER2, 3)],
+                Threshold::AbsoluteCount { weight: 3 },
+                voting_period,
+            )
+        };
+
+        // An empty allowlist would not allow any proposals
+        let err = instantiate(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            instantiate_msg.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::NoProposers {});
+        instantiate_msg.proposers = vec![SOMEBODY.into()];
+        instantiate(deps.as_mut(), mock_env(), info, instantiate_msg).unwrap();
+
+        let config = query_config(deps.as_ref()).unwrap();
+        assert_eq!(config.proposer, Proposer::Allowlist {});
+
+        // Only allowlisted addresses can propose, even members cannot
+        let proposal = ExecuteMsg::Propose {
+            title: "Pay somebody".to_string(),
+            description: "Do I pay her?".to_string(),
+            msgs: vec![],
+            earliest: None,
+            latest: None,
+        };
+        let info = mock_info(VOTER1, &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, proposal.clone()).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+        let info = mock_info(SOMEBODY, &[]);
+        execute(deps.as_mut(), mock_env(), info, proposal.clone()).unwrap();
+
+        // The allowlist is managed by the multisig itself
+        let update = ExecuteMsg::UpdateProposers {
+            add: vec![VOTER1.into()],
+            remove: vec![SOMEBODY.into()],
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(VOTER1, &[]),
+            update.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+        let info = mock_info(MOCK_CONTRACT_ADDR, &[]);
+        execute(deps.as_mut(), mock_env(), info.clone(), update).unwrap();
+        let res = list_proposers(deps.as_ref(), None, None).unwrap();
+        assert_eq!(res.proposers, vec![VOTER1.to_string()]);
+        let info_voter = mock_info(VOTER1, &[]);
+        execute(deps.as_mut(), mock_env(), info_voter, proposal.clone()).unwrap();
+
+        // Switch to members only
+        let update = ExecuteMsg::UpdateConfig(ConfigUpdate {
+            proposer: Some(Proposer::Member {}),
+            ..Default::default()
+        });
+        execute(deps.as_mut(), mock_env(), info.clone(), update).unwrap();
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(SOMEBODY, &[]),
+            proposal.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+        execute(deps.as_mut(), mock_env(), mock_info(VOTER2, &[]), proposal).unwrap();
+
+        // The allowlist can be emptied as long as it is not in use
+        let update = ExecuteMsg::UpdateProposers {
+            add: vec![],
+            remove: vec![VOTER1.into()],
+        };
+        execute(deps.as_mut(), mock_env(), info, update).unwrap();
+    }
+
+    #[test]
+    fn test_proposal_deposit_works() {
+        let mut deps = mock_dependencies(&[]);
+
+        let info = mock_info(OWNER, &[]);
+        setup_test_case(deps.as_mut(), info, 3, Duration::Time(2000000)).unwrap();
+        let proposal = ExecuteMsg::Propose {
+            title: "Pay somebody".to_string(),
+            description: "Do I pay her?".to_string(),
+            msgs: vec![],
+            earliest: None,
+            latest: None,
+        };
+
+        // Without a deposit, no funds can be sent along
+        let info = mock_info(SOMEBODY, &[coin(10, "ujuno")]);
+        let err = execute(deps.as_mut(), mock_env(), info, proposal.clone()).unwrap_err();
+        assert_eq!(err, ContractError::UnexpectedFunds {});
+
+        // Require a deposit that is kept if the proposal fails
+        let deposit = DepositInfo {
+            amount: coin(10, "ujuno"),
+            refund_failed: false,
+        };
+        let update = ExecuteMsg::UpdateConfig(ConfigUpdate {
+            deposit: Some(Some(deposit.clone())),
+            ..Default::default()
+        });
+        let info = mock_info(MOCK_CONTRACT_ADDR, &[]);
+        execute(deps.as_mut(), mock_env(), info, update).unwrap();
+        let config = query_config(deps.as_ref()).unwrap();
+        assert_eq!(config.deposit, Some(deposit));
+
+        // The deposit must be paid exactly
+        let wrong_funds = [vec![], vec![coin(5, "ujuno")], vec![coin(10, "uatom")]];
+        for funds in wrong_funds.iter() {
+            let info = mock_info(SOMEBODY, funds);
+            let err = execute(deps.as_mut(), mock_env(), info, proposal.clone()).unwrap_err();
+            assert_eq!(
+                err,
+                ContractError::InvalidDeposit {
+                    deposit: "10ujuno".to_string()
+                }
+            );
+        }
+
+        // Executing returns the deposit to the proposer
+        let info = mock_info(SOMEBODY, &[coin(10, "ujuno")]);
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), proposal.clone()).unwrap();
+        let passed_id: u64 = res.attributes[2].value.parse().unwrap();
+        let vote = ExecuteMsg::Vote {
+            proposal_id: passed_id,
+            vote: Vote::Yes,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info(VOTER3, &[]), vote).unwrap();
+        let execution = ExecuteMsg::Execute {
+            proposal_id: passed_id,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), execution).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: SOMEBODY.to_string(),
+                amount: vec![coin(10, "ujuno")],
+            })]
+        );
+
+        // A rejected proposal keeps the deposit once it is closed
+        let res = execute(deps.as_mut(), mock_env(), info, proposal).unwrap();
+        let rejected_id: u64 = res.attributes[2].value.parse().unwrap();
+        let no_vote = ExecuteMsg::Vote {
+            proposal_id: rejected_id,
+            vote: Vote::No,
+        };
+        for voter in [VOTER1, VOTER2, VOTER3, VOTER4, VOTER5].iter() {
+            execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info(voter, &[]),
+                no_vote.clone(),
+            )
+            .unwrap();
+        }
+        let closing = ExecuteMsg::Close {
+            proposal_id: rejected_id,
+        };
+        let info = mock_info(SOMEBODY, &[]);
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), closing.clone()).unwrap();
+        assert_eq!(res.messages, vec![]);
+        let err = execute(deps.as_mut(), mock_env(), info, closing).unwrap_err();
+        assert_eq!(err, ContractError::WrongCloseStatus {});
+    }
+
+    #[test]
+    fn test_close_refunds_deposit_of_expired_execution() {
+        let mut deps = mock_dependencies(&[]);
+
+        let info = mock_info(OWNER, &[]);
+        let instantiate_msg = InstantiateMsg {
+            execution_window: Some(Duration::Time(100)),
+            deposit: Some(DepositInfo {
+                amount: coin(10, "ujuno"),
+                refund_failed: false,
+            }),
+            track_failures: true,
+            ..init_msg(
+                vec![voter(OWNER, 1), voter(VOTER3, 3)],
+                Threshold::AbsoluteCount { weight: 3 },
+                Duration::Time(2000000),
+            )
+        };
+        instantiate(deps.as_mut(), mock_env(), info, instantiate_msg).unwrap();
+
+        let proposal = ExecuteMsg::Propose {
+            title: "Pay somebody".to_string(),
+            description: "Do I pay her?".to_string(),
+            msgs: vec![BankMsg::Send {
+                to_address: SOMEBODY.into(),
+                amount: vec![coin(1, "BTC")],
+            }
+            .into()],
+            earliest: None,
+            latest: None,
+        };
+        let info = mock_info(VOTER3, &[coin(10, "ujuno")]);
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), proposal.clone()).unwrap();
+        let passed_id: u64 = res.attributes[2].value.parse().unwrap();
+
+        // One fails when it is executed
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), proposal).unwrap();
+        let failed_id: u64 = res.attributes[2].value.parse().unwrap();
+        let execution = ExecuteMsg::Execute {
+            proposal_id: failed_id,
+        };
+        execute(deps.as_mut(), mock_env(), info, execution).unwrap();
+        // the failed dispatch is reverted, including its refund, and only reported here
+        let failure = Reply {
+            id: failed_id,
+            result: ContractResult::Err("insufficient funds".to_string()),
+        };
+        reply(deps.as_mut(), mock_env(), failure).unwrap();
+
+        let refund = vec![SubMsg::new(BankMsg::Send {
+            to_address: VOTER3.to_string(),
+            amount: vec![coin(10, "ujuno")],
+        })];
+        for proposal_id in [passed_id, failed_id].iter().copied() {
+            // Both can still be executed during the execution window
+            let closing = ExecuteMsg::Close { proposal_id };
+            let info = mock_info(SOMEBODY, &[]);
+            let err =
+                execute(deps.as_mut(), mock_env(), info.clone(), closing.clone()).unwrap_err();
+            assert_eq!(err, ContractError::WrongCloseStatus {});
+
+            // Once it is over, closing returns the deposit as they passed
+            let env = mock_env_time(100);
+            let res = execute(deps.as_mut(), env.clone(), info.clone(), closing.clone()).unwrap();
+            assert_eq!(res.messages, refund);
+            let status = query_proposal_status(deps.as_ref(), env.clone(), proposal_id).unwrap();
+            assert_eq!(status.status, Status::ExecutionExpired);
+            let err = execute(deps.as_mut(), env, info, closing).unwrap_err();
+            assert_eq!(err, ContractError::WrongCloseStatus {});
+        }
     }
 
     #[test]
-    fn test_instantiate_works() {
+    fn test_module_execution_works() {
         let mut deps = mock_dependencies(&[]);
-        let info = mock_info(OWNER, &[]);
 
-        let max_voting_period = Duration::Time(1234567);
+        let info = mock_info(OWNER, &[]);
+        setup_test_case(deps.as_mut(), info, 3, Duration::Time(2000000)).unwrap();
 
-        // No voters fails
-        let instantiate_msg = InstantiateMsg {
-            voters: vec![],
-            threshold_weight: 1,
-            max_voting_period,
+        let module = "payroll";
+        let payout = vec![BankMsg::Send {
+            to_address: SOMEBODY.into(),
+            amount: vec![coin(1, "BTC")],
+        }
+        .into()];
+        let module_execution = ExecuteMsg::ExecuteFromModule {
+            msgs: payout.clone(),
         };
-        let err =
-            instantiate(deps.as_mut(), mock_env(), info.clone(), instantiate_msg).unwrap_err();
-        assert_eq!(err, ContractError::NoVoters {});
 
-        // Zero required weight fails
-        let instantiate_msg = InstantiateMsg {
-            voters: vec![voter(OWNER, 1)],
-            threshold_weight: 0,
-            max_voting_period,
+        // Unknown contracts cannot execute anything
+        let info = mock_info(module, &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, module_execution.clone()).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        // Only the multisig itself can enable modules
+        let enable = ExecuteMsg::EnableModule {
+            module: module.into(),
         };
-        let err =
-            instantiate(deps.as_mut(), mock_env(), info.clone(), instantiate_msg).unwrap_err();
-        assert_eq!(err, ContractError::ZeroWeight {});
+        let info = mock_info(VOTER5, &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, enable.clone()).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+        let info = mock_info(MOCK_CONTRACT_ADDR, &[]);
+        execute(deps.as_mut(), mock_env(), info.clone(), enable).unwrap();
+        let res = list_modules(deps.as_ref(), None, None).unwrap();
+        assert_eq!(res.modules, vec![module.to_string()]);
 
-        // Total weight less than required weight not allowed
-        let threshold_weight = 100;
-        let err = setup_test_case(
+        // Enabled modules dispatch messages without a vote
+        let module_info = mock_info(module, &[]);
+        let res = execute(
             deps.as_mut(),
-            info.clone(),
-            threshold_weight,
-            max_voting_period,
+            mock_env(),
+            module_info.clone(),
+            module_execution.clone(),
         )
-        .unwrap_err();
-        assert_eq!(err, ContractError::UnreachableWeight {});
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            payout.into_iter().map(SubMsg::new).collect::<Vec<_>>()
+        );
 
-        // All valid
-        let threshold_weight = 1;
-        setup_test_case(deps.as_mut(), info, threshold_weight, max_voting_period).unwrap();
+        // Disabled modules lose that permission
+        let disable = ExecuteMsg::DisableModule {
+            module: module.into(),
+        };
+        execute(deps.as_mut(), mock_env(), info, disable).unwrap();
+        let err = execute(deps.as_mut(), mock_env(), module_info, module_execution).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+        let res = list_modules(deps.as_ref(), None, None).unwrap();
+        assert!(res.modules.is_empty());
     }
 
-    // TODO: query() tests
-
     #[test]
-    fn test_propose_works() {
-        let mut deps = mock_dependencies(&[]);
-
-        let threshold_weight = 4;
-        let voting_period = Duration::Time(2000000);
+    fn test_guard_works() {
+        const GUARD: &str = "guard";
+        const BLOCKED: &str = "blocked";
+        // the guard refuses any payments to the blocked address
+        let mut deps = mock_dependencies_with_wasm(|contract, msg| {
+            assert_eq!(contract, GUARD);
+            let GuardQueryMsg::<CustomMsg>::CheckMsgs { msgs, .. } = from_binary(msg)?;
+            let allowed = !msgs.iter().any(|msg| {
+                matches!(msg, CosmosMsg::Bank(BankMsg::Send { to_address, .. }) if to_address == BLOCKED)
+            });
+            to_binary(&GuardResponse { allowed })
+        });
 
         let info = mock_info(OWNER, &[]);
-        setup_test_case(deps.as_mut(), info, threshold_weight, voting_period).unwrap();
+        setup_test_case(deps.as_mut(), info, 3, Duration::Time(2000000)).unwrap();
+        let update = ExecuteMsg::UpdateConfig(ConfigUpdate {
+            guard: Some(Some(GUARD.into())),
+            ..Default::default()
+        });
+        let info = mock_info(MOCK_CONTRACT_ADDR, &[]);
+        execute(deps.as_mut(), mock_env(), info.clone(), update).unwrap();
+        let enable = ExecuteMsg::EnableModule {
+            module: VOTER1.into(),
+        };
+        execute(deps.as_mut(), mock_env(), info, enable).unwrap();
 
-        let bank_msg = BankMsg::Send {
-            to_address: SOMEBODY.into(),
-            amount: vec![coin(1, "BTC")],
+        let pay = |to_address: &str| -> Vec<CosmosMsg<CustomMsg>> {
+            vec![BankMsg::Send {
+                to_address: to_address.into(),
+                amount: vec![coin(1, "BTC")],
+            }
+            .into()]
         };
-        let msgs = vec![CosmosMsg::Bank(bank_msg)];
 
-        // Wrong expiration option fails
+        // Passed proposals are checked before they are executed
+        for (recipient, allowed) in [(SOMEBODY, true), (BLOCKED, false)].iter() {
+            let proposal = ExecuteMsg::Propose {
+                title: "Pay somebody".to_string(),
+                description: "Do I pay her?".to_string(),
+                msgs: pay(recipient),
+                earliest: None,
+                latest: None,
+            };
+            let info = mock_info(VOTER3, &[]);
+            let res = execute(deps.as_mut(), mock_env(), info.clone(), proposal).unwrap();
+            let proposal_id: u64 = res.attributes[2].value.parse().unwrap();
+            assert_eq!(res.attributes[3].value, "Passed");
+
+            let execution = ExecuteMsg::Execute { proposal_id };
+            let res = execute(deps.as_mut(), mock_env(), info, execution);
+            if *allowed {
+                res.unwrap();
+            } else {
+                assert_eq!(res.unwrap_err(), ContractError::GuardRejected {});
+                let prop = query_proposal(deps.as_ref(), mock_env(), proposal_id).unwrap();
+                assert_eq!(prop.status, Status::Passed);
+            }
+        }
+
+        // So are messages from modules
+        let info = mock_info(VOTER1, &[]);
+        let module_execution = ExecuteMsg::ExecuteFromModule {
+            msgs: pay(SOMEBODY),
+        };
+        execute(deps.as_mut(), mock_env(), info.clone(), module_execution).unwrap();
+        let module_execution = ExecuteMsg::ExecuteFromModule { msgs: pay(BLOCKED) };
+        let err = execute(deps.as_mut(), mock_env(), info, module_execution).unwrap_err();
+        assert_eq!(err, ContractError::GuardRejected {});
+    }
+
+    #[test]
+    fn test_hooks_work() {
+        let mut deps = mock_dependencies(&[]);
+
         let info = mock_info(OWNER, &[]);
-        let proposal_wrong_exp = ExecuteMsg::Propose {
-            title: "Rewarding somebody".to_string(),
-            description: "Do we reward her?".to_string(),
-            msgs: msgs.clone(),
-            latest: Some(Expiration::AtHeight(123456)),
+        setup_test_case(deps.as_mut(), info, 3, Duration::Time(2000000)).unwrap();
+
+        // Only the multisig itself can register hooks
+        const HOOK: &str = "notifier";
+        let add_hook = ExecuteMsg::AddHook { addr: HOOK.into() };
+        let info = mock_info(VOTER5, &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, add_hook.clone()).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+        let info = mock_info(MOCK_CONTRACT_ADDR, &[]);
+        execute(deps.as_mut(), mock_env(), info.clone(), add_hook.clone()).unwrap();
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), add_hook).unwrap_err();
+        assert_eq!(err, ContractError::HookAlreadyRegistered {});
+        assert_eq!(list_hooks(deps.as_ref()).unwrap().hooks, vec![HOOK]);
+
+        let hook = |msg: HookExecuteMsg| {
+            SubMsg::reply_on_error(
+                WasmMsg::Execute {
+                    contract_addr: HOOK.into(),
+                    msg: to_binary(&msg).unwrap(),
+                    funds: vec![],
+                },
+                HOOK_REPLY_ID,
+            )
         };
-        let err = execute(deps.as_mut(), mock_env(), info, proposal_wrong_exp).unwrap_err();
-        assert_eq!(err, ContractError::WrongExpiration {});
 
-        // Anyone can propose
-        let info = mock_info(SOMEBODY, &[]);
+        // New proposals are announced
         let proposal = ExecuteMsg::Propose {
-            title: "Rewarding somebody".to_string(),
-            description: "Do we reward her?".to_string(),
-            msgs: msgs.clone(),
+            title: "Pay somebody".to_string(),
+            description: "Do I pay her?".to_string(),
+            msgs: vec![],
+            earliest: None,
             latest: None,
         };
-        let res = execute(deps.as_mut(), mock_env(), info, proposal.clone()).unwrap();
+        let res = execute(deps.as_mut(), mock_env(), mock_info(VOTER1, &[]), proposal).unwrap();
+        let proposal_id: u64 = res.attributes[2].value.parse().unwrap();
         assert_eq!(
-            res,
-            Response::new()
-                .add_attribute("action", "propose")
-                .add_attribute("sender", SOMEBODY)
-                .add_attribute("proposal_id", 1.to_string())
-                .add_attribute("status", "Open")
+            res.messages,
+            vec![hook(HookExecuteMsg::ProposalHook(
+                ProposalHook::NewProposal {
+                    id: proposal_id,
+                    proposer: VOTER1.into(),
+                }
+            ))]
         );
 
-        // Proposal from voter works
-        let info = mock_info(VOTER3, &[]);
-        let res = execute(deps.as_mut(), mock_env(), info, proposal.clone()).unwrap();
+        // And retracted votes
+        let no_vote = ExecuteMsg::Vote {
+            proposal_id,
+            vote: Vote::No,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info(VOTER3, &[]), no_vote).unwrap();
+        let retract = ExecuteMsg::Retract { proposal_id };
+        let res = execute(deps.as_mut(), mock_env(), mock_info(VOTER3, &[]), retract).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![hook(HookExecuteMsg::VoteHook(VoteHook::RetractedVote {
+                proposal_id,
+                voter: VOTER3.into(),
+            }))]
+        );
 
-        // Verify
+        // So are votes and the status changes they cause
+        let vote = ExecuteMsg::Vote {
+            proposal_id,
+            vote: Vote::Yes,
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info(VOTER2, &[]), vote).unwrap();
         assert_eq!(
-            res,
-            Response::new()
-                .add_attribute("action", "propose")
-                .add_attribute("sender", VOTER3)
-                .add_attribute("proposal_id", 2.to_string())
-                .add_attribute("status", "Open")
+            res.messages,
+            vec![
+                hook(HookExecuteMsg::VoteHook(VoteHook::NewVote {
+                    proposal_id,
+                    voter: VOTER2.into(),
+                    vote: Vote::Yes,
+                })),
+                hook(HookExecuteMsg::ProposalHook(ProposalHook::StatusChanged {
+                    id: proposal_id,
+                    old_status: Status::Open,
+                    new_status: Status::Passed,
+                })),
+            ]
         );
 
-        // Proposal from voter with enough vote power directly passes
-        let info = mock_info(VOTER4, &[]);
-        let res = execute(deps.as_mut(), mock_env(), info, proposal).unwrap();
+        let execution = ExecuteMsg::Execute { proposal_id };
+        let res = execute(deps.as_mut(), mock_env(), mock_info(VOTER2, &[]), execution).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![hook(HookExecuteMsg::ProposalHook(
+                ProposalHook::StatusChanged {
+                    id: proposal_id,
+                    old_status: Status::Passed,
+                    new_status: Status::Executed,
+                }
+            ))]
+        );
 
-        // Verify
+        // A failing hook does not block the multisig
+        let failure = Reply {
+            id: HOOK_REPLY_ID,
+            result: ContractResult::Err("hook failed".to_string()),
+        };
+        let res = reply(deps.as_mut(), mock_env(), failure).unwrap();
+        assert_eq!(res.attributes[0].value, "hook_failed");
+
+        // Pending proposals are reported as open by the first vote once voting started
+        let pending = ExecuteMsg::Propose {
+            title: "Pay somebody later".to_string(),
+            description: "Do I pay her?".to_string(),
+            msgs: vec![],
+            earliest: Some(Expiration::AtTime(mock_env_time(5).block.time)),
+            latest: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info(VOTER1, &[]), pending).unwrap();
+        let pending_id: u64 = res.attributes[2].value.parse().unwrap();
+        let no_vote = ExecuteMsg::Vote {
+            proposal_id: pending_id,
+            vote: Vote::No,
+        };
+        let env = mock_env_time(5);
+        let res = execute(deps.as_mut(), env, mock_info(VOTER3, &[]), no_vote).unwrap();
         assert_eq!(
-            res,
-            Response::new()
-                .add_attribute("action", "propose")
-                .add_attribute("sender", VOTER4)
-                .add_attribute("proposal_id", 3.to_string())
-                .add_attribute("status", "Passed")
+            res.messages,
+            vec![
+                hook(HookExecuteMsg::VoteHook(VoteHook::NewVote {
+                    proposal_id: pending_id,
+                    voter: VOTER3.into(),
+                    vote: Vote::No,
+                })),
+                hook(HookExecuteMsg::ProposalHook(ProposalHook::StatusChanged {
+                    id: pending_id,
+                    old_status: Status::Pending,
+                    new_status: Status::Open,
+                })),
+            ]
         );
+
+        // Removed hooks are no longer notified
+        let remove_hook = ExecuteMsg::RemoveHook { addr: HOOK.into() };
+        execute(deps.as_mut(), mock_env(), info.clone(), remove_hook.clone()).unwrap();
+        let err = execute(deps.as_mut(), mock_env(), info, remove_hook).unwrap_err();
+        assert_eq!(err, ContractError::HookNotRegistered {});
+        assert!(list_hooks(deps.as_ref()).unwrap().hooks.is_empty());
     }
 
     #[test]
-    fn test_vote_works() {
+    fn test_group_membership_works() {
+        const GROUP: &str = "group";
+        let join_height = mock_env().block.height + 10;
+        // VOTER3 only joins the group at join_height
+        let mut deps = mock_dependencies_with_wasm(move |contract, msg| {
+            assert_eq!(contract, GROUP);
+            let weight = |addr: &str, height: u64| match addr {
+                OWNER => Some(1),
+                VOTER1 => Some(2),
+                VOTER2 => Some(3),
+                VOTER3 if height > join_height => Some(4),
+                _ => None,
+            };
+            match from_binary(msg)? {
+                Cw4QueryMsg::TotalWeight {} => to_binary(&TotalWeightResponse { weight: 6 }),
+                Cw4QueryMsg::ListMembers { .. } => to_binary(&MemberListResponse {
+                    members: vec![Member {
+                        addr: OWNER.into(),
+                        weight: 1,
+                    }],
+                }),
+                Cw4QueryMsg::Member { addr, at_height } => to_binary(&MemberResponse {
+                    weight: weight(&addr, at_height.unwrap_or(u64::MAX)),
+                }),
+            }
+        });
+
+        // Voters come from either the group or the list
+        let info = mock_info(OWNER, &[]);
+        let mut instantiate_msg = InstantiateMsg {
+            group: Some(GROUP.into()),
+            proposer: Proposer::Member {},
+            ..init_msg(
+                vec![voter(OWNER, 1)],
+                Threshold::AbsoluteCount { weight: 4 },
+                Duration::Time(2000000),
+            )
+        };
+        let err = instantiate(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            instantiate_msg.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::GroupMembers {});
+        instantiate_msg.voters = vec![];
+        instantiate(deps.as_mut(), mock_env(), info, instantiate_msg).unwrap();
+
+        // The group determines the total weight and the voters
+        let threshold = query_threshold(deps.as_ref()).unwrap();
+        assert_eq!(
+            threshold,
+            ThresholdResponse::AbsoluteCount {
+                weight: 4,
+                total_weight: 6
+            }
+        );
+        let voter3 = query_voter(deps.as_ref(), VOTER3.into()).unwrap();
+        assert_eq!(voter3.weight, Some(4));
+        let voters = list_voters(deps.as_ref(), None, None).unwrap();
+        assert_eq!(voters.voters.len(), 1);
+
+        let proposal = ExecuteMsg::Propose {
+            title: "Pay somebody".to_string(),
+            description: "Do I pay her?".to_string(),
+            msgs: vec![],
+            earliest: None,
+            latest: None,
+        };
+        let info = mock_info(VOTER1, &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, proposal.clone()).unwrap();
+        let proposal_id: u64 = res.attributes[2].value.parse().unwrap();
+
+        // Members that joined after the proposal was created cannot vote on it
+        let env = mock_env_height(20);
+        let vote = ExecuteMsg::Vote {
+            proposal_id,
+            vote: Vote::Yes,
+        };
+        let info = mock_info(VOTER3, &[]);
+        let err = execute(deps.as_mut(), env.clone(), info.clone(), vote).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        // But they can on later proposals
+        let res = execute(deps.as_mut(), env.clone(), info, proposal).unwrap();
+        assert_eq!(res.attributes[3].value, "Passed");
+
+        // Members can only be changed in the group itself
+        let update = ExecuteMsg::UpdateMembers {
+            add: vec![voter(VOTER4, 4)],
+            remove: vec![],
+        };
+        let info = mock_info(MOCK_CONTRACT_ADDR, &[]);
+        let err = execute(deps.as_mut(), env, info, update).unwrap_err();
+        assert_eq!(err, ContractError::GroupMembers {});
+    }
+
+    #[test]
+    fn test_migrate_works() {
         let mut deps = mock_dependencies(&[]);
 
-        let threshold_weight = 3;
+        // Set up the storage of a 0.1 contract with an open proposal
         let voting_period = Duration::Time(2000000);
+        let old_cfg = ConfigV0_1 {
+            threshold_weight: 3,
+            total_weight: 3,
+            max_voting_period: voting_period,
+        };
+        CONFIG_V0_1.save(&mut deps.storage, &old_cfg).unwrap();
+        // 0.1 kept the voters in a plain map, without a history of changes
+        let voters_v0_1: Map<&Addr, u64> = Map::new("voters");
+        voters_v0_1
+            .save(&mut deps.storage, &Addr::unchecked(OWNER), &1)
+            .unwrap();
+        voters_v0_1
+            .save(&mut deps.storage, &Addr::unchecked(VOTER1), &2)
+            .unwrap();
+        let old_prop = ProposalV0_1 {
+            title: "Pay somebody".to_string(),
+            description: "Do I pay her?".to_string(),
+            expires: voting_period.after(&mock_env().block),
+            msgs: vec![BankMsg::Send {
+                to_address: SOMEBODY.into(),
+                amount: vec![coin(600, "ujuno")],
+            }
+            .into()],
+            status: Status::Open,
+            voters: vec![Addr::unchecked(OWNER)],
+            yes_weight: 1,
+        };
+        PROPOSALS_V0_1
+            .save(&mut deps.storage, 1.into(), &old_prop)
+            .unwrap();
+        consume_next_id(&mut deps.storage).unwrap();
+
+        let res = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+        assert_eq!(res.attributes[1].value, CONTRACT_VERSION);
+        let version = CONTRACT.load(&deps.storage).unwrap();
+        assert_eq!(version.contract, CONTRACT_NAME);
+
+        // Old proposals and their votes carry over
+        let prop = query_proposal(deps.as_ref(), mock_env(), 1).unwrap();
+        assert_eq!(prop.status, Status::Open);
+        assert_eq!(
+            prop.threshold,
+            ThresholdResponse::AbsoluteCount {
+                weight: 3,
+                total_weight: 3
+            }
+        );
+        let vote = query_vote(deps.as_ref(), 1, OWNER.into()).unwrap();
+        assert_eq!(vote.vote.unwrap().vote, Vote::Yes);
+        let res = query_treasury(deps.as_ref(), mock_env()).unwrap();
+        assert_eq!(res.committed, vec![coin(600, "ujuno")]);
+        let vote = ExecuteMsg::Vote {
+            proposal_id: 1,
+            vote: Vote::Yes,
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info(VOTER1, &[]), vote).unwrap();
+        assert_eq!(res.attributes[3].value, "Passed");
+
+        // Migrating to the same version is a no-op
+        migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+
+        // Downgrades and other contracts are rejected
+        set_contract_version(&mut deps.storage, CONTRACT_NAME, "99.0.0").unwrap();
+        let err = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::Downgrade {
+                stored: "99.0.0".to_string(),
+                current: CONTRACT_VERSION.to_string()
+            }
+        );
+        set_contract_version(&mut deps.storage, "crates.io:cw20-base", "0.1.0").unwrap();
+        let err = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::WrongContract {
+                contract: "crates.io:cw20-base".to_string()
+            }
+        );
+
+        // Versions without a known layout are refused
+        set_contract_version(&mut deps.storage, CONTRACT_NAME, "0.0.1").unwrap();
+        let err = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::UnsupportedVersion {
+                version: "0.0.1".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_execution_failures_are_tracked() {
+        let mut deps = mock_dependencies(&[]);
 
         let info = mock_info(OWNER, &[]);
-        setup_test_case(deps.as_mut(), info.clone(), threshold_weight, voting_period).unwrap();
+        setup_test_case(deps.as_mut(), info, 3, Duration::Time(2000000)).unwrap();
+        let update = ExecuteMsg::UpdateConfig(ConfigUpdate {
+            track_failures: Some(true),
+            ..Default::default()
+        });
+        let contract_info = mock_info(MOCK_CONTRACT_ADDR, &[]);
+        execute(deps.as_mut(), mock_env(), contract_info.clone(), update).unwrap();
 
-        // Propose
-        let bank_msg = BankMsg::Send {
+        let payout: Vec<CosmosMsg<CustomMsg>> = vec![BankMsg::Send {
             to_address: SOMEBODY.into(),
             amount: vec![coin(1, "BTC")],
-        };
-        let msgs = vec![CosmosMsg::Bank(bank_msg)];
+        }
+        .into()];
         let proposal = ExecuteMsg::Propose {
             title: "Pay somebody".to_string(),
             description: "Do I pay her?".to_string(),
-            msgs,
+            msgs: payout.clone(),
+            earliest: None,
             latest: None,
         };
+        let info = mock_info(VOTER3, &[]);
         let res = execute(deps.as_mut(), mock_env(), info.clone(), proposal).unwrap();
-
-        // Get the proposal id from the logs
         let proposal_id: u64 = res.attributes[2].value.parse().unwrap();
 
-        // Owner cannot vote (again)
-        let yes_vote = ExecuteMsg::Vote {
-            proposal_id,
-            vote: Vote::Yes,
+        // The messages are dispatched through a call to the multisig itself
+        let execution = ExecuteMsg::Execute { proposal_id };
+        let dispatch = ExecuteMsg::DispatchProposal { proposal_id };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), execution.clone()).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::reply_on_error(
+                WasmMsg::Execute {
+                    contract_addr: MOCK_CONTRACT_ADDR.into(),
+                    msg: to_binary(&dispatch).unwrap(),
+                    funds: vec![],
+                },
+                proposal_id
+            )]
+        );
+        // It is only executed once they went through
+        let prop = query_proposal(deps.as_ref(), mock_env(), proposal_id).unwrap();
+        assert_eq!(prop.status, Status::Passed);
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), dispatch.clone()).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        // A failing message reverts the dispatch and marks the proposal as failed,
+        // keeping the error
+        let failure = Reply {
+            id: proposal_id,
+            result: ContractResult::Err("insufficient funds".to_string()),
         };
-        let err = execute(deps.as_mut(), mock_env(), info, yes_vote.clone()).unwrap_err();
-        assert_eq!(err, ContractError::AlreadyVoted {});
+        reply(deps.as_mut(), mock_env(), failure.clone()).unwrap();
+        let status = query_proposal_status(deps.as_ref(), mock_env(), proposal_id).unwrap();
+        assert_eq!(status.status, Status::ExecutionFailed);
+        // which cw3 clients see as passed, as it can be executed again
+        let prop = query_proposal(deps.as_ref(), mock_env(), proposal_id).unwrap();
+        assert_eq!(prop.status, Status::Passed);
+        let error = query_execution_failure(deps.as_ref(), proposal_id).unwrap();
+        assert_eq!(error.error, Some("insufficient funds".to_string()));
 
-        // Only voters can vote
-        let info = mock_info(SOMEBODY, &[]);
-        let err = execute(deps.as_mut(), mock_env(), info, yes_vote.clone()).unwrap_err();
+        // Only the proposal being dispatched can fail
+        let err = reply(deps.as_mut(), mock_env(), failure.clone()).unwrap_err();
+        assert_eq!(err, ContractError::UnknownReplyId { id: proposal_id });
+
+        // It can be retried
+        execute(deps.as_mut(), mock_env(), info, execution).unwrap();
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            contract_info.clone(),
+            dispatch.clone(),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::reply_on_success(payout[0].clone(), proposal_id)]
+        );
+        let prop = query_proposal(deps.as_ref(), mock_env(), proposal_id).unwrap();
+        assert_eq!(prop.status, Status::Executed);
+
+        // Once executed, it cannot be dispatched or fail again
+        let err = execute(deps.as_mut(), mock_env(), contract_info, dispatch).unwrap_err();
         assert_eq!(err, ContractError::Unauthorized {});
+        let err = reply(deps.as_mut(), mock_env(), failure).unwrap_err();
+        assert_eq!(err, ContractError::UnknownReplyId { id: proposal_id });
+    }
 
-        // But voter1 can
-        let info = mock_info(VOTER1, &[]);
-        let res = execute(deps.as_mut(), mock_env(), info, yes_vote.clone()).unwrap();
+    #[test]
+    fn test_tracked_execution_notifies_hooks_once_dispatched() {
+        let mut deps = mock_dependencies(&[]);
 
-        // Verify
+        let info = mock_info(OWNER, &[]);
+        setup_test_case(deps.as_mut(), info, 3, Duration::Time(2000000)).unwrap();
+        let contract_info = mock_info(MOCK_CONTRACT_ADDR, &[]);
+        let update = ExecuteMsg::UpdateConfig(ConfigUpdate {
+            track_failures: Some(true),
+            ..Default::default()
+        });
+        execute(deps.as_mut(), mock_env(), contract_info.clone(), update).unwrap();
+        const HOOK: &str = "notifier";
+        let add_hook = ExecuteMsg::AddHook { addr: HOOK.into() };
+        execute(deps.as_mut(), mock_env(), contract_info.clone(), add_hook).unwrap();
+
+        let proposal = ExecuteMsg::Propose {
+            title: "Pay somebody".to_string(),
+            description: "Do I pay her?".to_string(),
+            msgs: vec![],
+            earliest: None,
+            latest: None,
+        };
+        let info = mock_info(VOTER3, &[]);
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), proposal).unwrap();
+        let proposal_id: u64 = res.attributes[2].value.parse().unwrap();
+
+        // Executing only starts the dispatch
+        let execution = ExecuteMsg::Execute { proposal_id };
+        let res = execute(deps.as_mut(), mock_env(), info, execution).unwrap();
+        assert_eq!(res.messages.len(), 1);
+
+        // Which reports the status change once the messages went through
+        let dispatch = ExecuteMsg::DispatchProposal { proposal_id };
+        let res = execute(deps.as_mut(), mock_env(), contract_info, dispatch).unwrap();
+        let status_changed = HookExecuteMsg::ProposalHook(ProposalHook::StatusChanged {
+            id: proposal_id,
+            old_status: Status::Passed,
+            new_status: Status::Executed,
+        });
         assert_eq!(
-            res,
-            Response::new()
-                .add_attribute("action", "vote")
-                .add_attribute("sender", VOTER1)
-                .add_attribute("proposal_id", proposal_id.to_string())
-                .add_attribute("status", "Open")
+            res.messages,
+            vec![SubMsg::reply_on_error(
+                WasmMsg::Execute {
+                    contract_addr: HOOK.into(),
+                    msg: to_binary(&status_changed).unwrap(),
+                    funds: vec![],
+                },
+                HOOK_REPLY_ID,
+            )]
         );
+    }
 
-        // No/Veto votes have no effect on the tally
-        // Get the proposal id from the logs
-        let proposal_id: u64 = res.attributes[2].value.parse().unwrap();
-
-        // Compute the current tally
-        let tally = get_tally(deps.as_ref(), proposal_id);
+    #[test]
+    fn test_execution_receipts_work() {
+        let mut deps = mock_dependencies(&[]);
 
-        // Cast a No vote
-        let no_vote = ExecuteMsg::Vote {
-            proposal_id,
-            vote: Vote::No,
-        };
-        let info = mock_info(VOTER2, &[]);
-        execute(deps.as_mut(), mock_env(), info, no_vote.clone()).unwrap();
+        let info = mock_info(OWNER, &[]);
+        setup_test_case(deps.as_mut(), info, 3, Duration::Time(2000000)).unwrap();
 
-        // Cast a Veto vote
-        let veto_vote = ExecuteMsg::Vote {
-            proposal_id,
-            vote: Vote::Veto,
+        let instantiation: CosmosMsg<CustomMsg> = WasmMsg::Instantiate {
+            admin: None,
+            code_id: 1,
+            msg: Binary::from(b"{}".to_vec()),
+            funds: vec![],
+            label: "payroll".to_string(),
+        }
+        .into();
+        let proposal = ExecuteMsg::Propose {
+            title: "Set up payroll".to_string(),
+            description: "Instantiate a payroll contract".to_string(),
+            msgs: vec![instantiation.clone()],
+            earliest: None,
+            latest: None,
         };
         let info = mock_info(VOTER3, &[]);
-        execute(deps.as_mut(), mock_env(), info.clone(), veto_vote).unwrap();
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), proposal).unwrap();
+        let proposal_id: u64 = res.attributes[2].value.parse().unwrap();
 
-        // Verify
-        assert_eq!(tally, get_tally(deps.as_ref(), proposal_id));
+        // Nothing is recorded before execution
+        let res = query_execution_receipt(deps.as_ref(), proposal_id).unwrap();
+        assert!(res.receipts.is_empty());
 
-        // Once voted, yes votes cannot be changed
-        let info = mock_info(VOTER1, &[]);
-        let err = execute(deps.as_mut(), mock_env(), info.clone(), yes_vote.clone()).unwrap_err();
-        assert_eq!(err, ContractError::AlreadyVoted {});
-        assert_eq!(tally, get_tally(deps.as_ref(), proposal_id));
+        // Each message reports back its result
+        let execution = ExecuteMsg::Execute { proposal_id };
+        let res = execute(deps.as_mut(), mock_env(), info, execution).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::reply_on_success(instantiation, proposal_id)]
+        );
 
-        // Expired proposals cannot be voted
-        let env = match voting_period {
-            Duration::Time(duration) => mock_env_time(duration + 1),
-            Duration::Height(duration) => mock_env_height(duration + 1),
+        let receipt = SubMsgExecutionResponse {
+            events: vec![
+                Event::new("instantiate").add_attribute("_contract_address", "payroll_contract")
+            ],
+            data: Some(Binary::from(b"payroll_contract".to_vec())),
         };
-        let err = execute(deps.as_mut(), env, info, no_vote).unwrap_err();
-        assert_eq!(err, ContractError::Expired {});
-
-        // Vote it again, so it passes
-        let info = mock_info(VOTER4, &[]);
-        let res = execute(deps.as_mut(), mock_env(), info, yes_vote.clone()).unwrap();
+        let result = Reply {
+            id: proposal_id,
+            result: ContractResult::Ok(receipt.clone()),
+        };
+        reply(deps.as_mut(), mock_env(), result).unwrap();
+        let res = query_execution_receipt(deps.as_ref(), proposal_id).unwrap();
+        assert_eq!(res.receipts, vec![receipt]);
 
-        // Verify
+        // Unknown proposals are not accepted
+        let result = Reply {
+            id: proposal_id + 1,
+            result: ContractResult::Ok(SubMsgExecutionResponse {
+                events: vec![],
+                data: None,
+            }),
+        };
+        let err = reply(deps.as_mut(), mock_env(), result).unwrap_err();
         assert_eq!(
-            res,
-            Response::new()
-                .add_attribute("action", "vote")
-                .add_attribute("sender", VOTER4)
-                .add_attribute("proposal_id", proposal_id.to_string())
-                .add_attribute("status", "Passed")
+            err,
+            ContractError::UnknownReplyId {
+                id: proposal_id + 1
+            }
         );
-
-        // non-Open proposals cannot be voted
-        let info = mock_info(VOTER5, &[]);
-        let err = execute(deps.as_mut(), mock_env(), info, yes_vote).unwrap_err();
-        assert_eq!(err, ContractError::NotOpen {});
     }
 
+    #[cfg(feature = "token-factory")]
     #[test]
-    fn test_execute_works() {
-        let mut deps = mock_dependencies(&[]);
+    fn test_custom_messages_work() {
+        use crate::token_factory::TokenMsg;
+        use cosmwasm_std::Uint128;
 
-        let threshold_weight = 3;
-        let voting_period = Duration::Time(2000000);
+        let mut deps = mock_dependencies(&[]);
 
         let info = mock_info(OWNER, &[]);
-        setup_test_case(deps.as_mut(), info.clone(), threshold_weight, voting_period).unwrap();
+        setup_test_case(deps.as_mut(), info, 3, Duration::Time(2000000)).unwrap();
 
-        // Propose
-        let bank_msg = BankMsg::Send {
-            to_address: SOMEBODY.into(),
-            amount: vec![coin(1, "BTC")],
-        };
-        let msgs = vec![CosmosMsg::Bank(bank_msg)];
+        let mint: CosmosMsg<CustomMsg> = TokenMsg::MintTokens {
+            denom: format!("factory/{}/ujuno", MOCK_CONTRACT_ADDR),
+            amount: Uint128::new(1000),
+            mint_to_address: SOMEBODY.into(),
+        }
+        .into();
         let proposal = ExecuteMsg::Propose {
-            title: "Pay somebody".to_string(),
-            description: "Do I pay her?".to_string(),
-            msgs: msgs.clone(),
+            title: "Mint tokens".to_string(),
+            description: "Mint some tokens for somebody".to_string(),
+            msgs: vec![mint.clone()],
+            earliest: None,
             latest: None,
         };
+        let info = mock_info(VOTER3, &[]);
         let res = execute(deps.as_mut(), mock_env(), info.clone(), proposal).unwrap();
-
-        // Get the proposal id from the logs
         let proposal_id: u64 = res.attributes[2].value.parse().unwrap();
 
-        // Only Passed can be executed
+        // Custom messages are stored and dispatched like any other message
+        let prop = query_proposal(deps.as_ref(), mock_env(), proposal_id).unwrap();
+        assert_eq!(prop.msgs, vec![mint.clone()]);
         let execution = ExecuteMsg::Execute { proposal_id };
-        let err = execute(deps.as_mut(), mock_env(), info, execution.clone()).unwrap_err();
-        assert_eq!(err, ContractError::WrongExecuteStatus {});
+        let res = execute(deps.as_mut(), mock_env(), info, execution).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::reply_on_success(mint, proposal_id)]
+        );
+    }
 
-        // Vote it, so it passes
-        let vote = ExecuteMsg::Vote {
-            proposal_id,
-            vote: Vote::Yes,
+    #[test]
+    fn test_allowances_work() {
+        let mut deps = mock_dependencies(&[]);
+
+        let info = mock_info(OWNER, &[]);
+        setup_test_case(deps.as_mut(), info, 3, Duration::Time(2000000)).unwrap();
+
+        // Only the multisig itself can grant allowances
+        let set_allowance = ExecuteMsg::SetAllowance {
+            spender: VOTER1.into(),
+            amount: coin(100, "ujuno"),
+            reset_period: Duration::Height(10),
         };
-        let info = mock_info(VOTER3, &[]);
-        let res = execute(deps.as_mut(), mock_env(), info.clone(), vote).unwrap();
+        let info = mock_info(VOTER5, &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, set_allowance.clone()).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
 
-        // Verify
+        // Allowances must be worth something
+        let info = mock_info(MOCK_CONTRACT_ADDR, &[]);
+        let empty_allowance = ExecuteMsg::SetAllowance {
+            spender: VOTER1.into(),
+            amount: coin(0, "ujuno"),
+            reset_period: Duration::Height(10),
+        };
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), empty_allowance).unwrap_err();
+        assert_eq!(err, ContractError::InvalidAllowance {});
+        execute(deps.as_mut(), mock_env(), info.clone(), set_allowance).unwrap();
+
+        // Only the spender can spend their allowance
+        let spend = ExecuteMsg::SpendAllowance {
+            to: SOMEBODY.into(),
+            amount: coin(60, "ujuno"),
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(VOTER2, &[]),
+            spend.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        // Without sending funds or spending nothing
+        let info_funds = mock_info(VOTER1, &[coin(1, "ujuno")]);
+        let err = execute(deps.as_mut(), mock_env(), info_funds, spend.clone()).unwrap_err();
+        assert_eq!(err, ContractError::UnexpectedFunds {});
+        let spend_nothing = ExecuteMsg::SpendAllowance {
+            to: SOMEBODY.into(),
+            amount: coin(0, "ujuno"),
+        };
+        let info_voter = mock_info(VOTER1, &[]);
+        let err = execute(deps.as_mut(), mock_env(), info_voter, spend_nothing).unwrap_err();
+        assert_eq!(err, ContractError::ZeroAmount {});
+
+        let res = execute(deps.as_mut(), mock_env(), mock_info(VOTER1, &[]), spend).unwrap();
         assert_eq!(
-            res,
-            Response::new()
-                .add_attribute("action", "vote")
-                .add_attribute("sender", VOTER3)
-                .add_attribute("proposal_id", proposal_id.to_string())
-                .add_attribute("status", "Passed")
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: SOMEBODY.into(),
+                amount: vec![coin(60, "ujuno")],
+            })]
         );
+        let res = query_allowance(deps.as_ref(), mock_env(), VOTER1.into(), "ujuno".into())
+            .unwrap()
+            .allowance
+            .unwrap();
+        assert_eq!(res.remaining, coin(40, "ujuno"));
+        assert_eq!(res.resets, Duration::Height(10).after(&mock_env().block));
 
-        // In passing: Try to close Passed fails
-        let closing = ExecuteMsg::Close { proposal_id };
-        let err = execute(deps.as_mut(), mock_env(), info, closing).unwrap_err();
-        assert_eq!(err, ContractError::WrongCloseStatus {});
-
-        // Execute works. Anybody can execute Passed proposals
-        let info = mock_info(SOMEBODY, &[]);
-        let res = execute(deps.as_mut(), mock_env(), info.clone(), execution).unwrap();
+        // The allowance cannot be exceeded within a period
+        let spend = ExecuteMsg::SpendAllowance {
+            to: SOMEBODY.into(),
+            amount: coin(50, "ujuno"),
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(VOTER1, &[]),
+            spend.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::AllowanceExceeded {
+                remaining: "40ujuno".to_string()
+            }
+        );
 
-        // Verify
+        // It resets at the end of every period, even if nothing was spent for a while
+        let env = mock_env_height(25);
+        let res = query_allowance(deps.as_ref(), env.clone(), VOTER1.into(), "ujuno".into())
+            .unwrap()
+            .allowance
+            .unwrap();
+        assert_eq!(res.remaining, coin(100, "ujuno"));
+        assert_eq!(res.resets, Duration::Height(30).after(&mock_env().block));
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info(VOTER1, &[]),
+            spend.clone(),
+        )
+        .unwrap();
+        let res = list_allowances(deps.as_ref(), env.clone(), VOTER1.into(), None, None).unwrap();
         assert_eq!(
-            res,
-            Response::new()
-                .add_messages(msgs)
-                .add_attribute("action", "execute")
-                .add_attribute("sender", SOMEBODY)
-                .add_attribute("proposal_id", proposal_id.to_string())
+            res.allowances,
+            vec![AllowanceInfo {
+                amount: coin(100, "ujuno"),
+                remaining: coin(50, "ujuno"),
+                reset_period: Duration::Height(10),
+                resets: Duration::Height(30).after(&mock_env().block),
+            }]
         );
 
-        // In passing: Try to close Executed fails
-        let closing = ExecuteMsg::Close { proposal_id };
-        let err = execute(deps.as_mut(), mock_env(), info, closing).unwrap_err();
-        assert_eq!(err, ContractError::WrongCloseStatus {});
+        // Removed allowances cannot be spent anymore
+        let remove_allowance = ExecuteMsg::RemoveAllowance {
+            spender: VOTER1.into(),
+            denom: "ujuno".into(),
+        };
+        execute(deps.as_mut(), env.clone(), info, remove_allowance).unwrap();
+        let res = query_allowance(deps.as_ref(), env.clone(), VOTER1.into(), "ujuno".into());
+        assert_eq!(res.unwrap().allowance, None);
+        let err = execute(deps.as_mut(), env, mock_info(VOTER1, &[]), spend).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
     }
 
     #[test]
-    fn test_close_works() {
-        let mut deps = mock_dependencies(&[]);
+    fn test_allowance_periods() {
+        // Time periods stay aligned to when the allowance was granted
+        let mut allowance =
+            Allowance::new(Uint128::new(100), Duration::Time(100), &mock_env().block);
+        allowance.spent = Uint128::new(30);
+        let allowance = allowance.current(&mock_env_time(250).block);
+        assert_eq!(allowance.spent, Uint128::zero());
+        assert_eq!(
+            allowance.resets,
+            Duration::Time(300).after(&mock_env().block)
+        );
 
-        let threshold_weight = 3;
-        let voting_period = Duration::Height(2000000);
+        // A period end in another unit than the period starts a new period at the block
+        let allowance = Allowance {
+            amount: Uint128::new(100),
+            spent: Uint128::new(30),
+            reset_period: Duration::Time(100),
+            resets: Duration::Height(10).after(&mock_env().block),
+        };
+        let env = mock_env_height(10);
+        let allowance = allowance.current(&env.block);
+        assert_eq!(allowance.spent, Uint128::zero());
+        assert_eq!(allowance.resets, Duration::Time(100).after(&env.block));
+    }
+
+    #[test]
+    fn test_treasury_works() {
+        let mut deps = mock_dependencies(&[coin(1000, "ujuno")]);
 
         let info = mock_info(OWNER, &[]);
-        setup_test_case(deps.as_mut(), info.clone(), threshold_weight, voting_period).unwrap();
+        setup_test_case(deps.as_mut(), info, 3, Duration::Height(10)).unwrap();
+        let update = ExecuteMsg::UpdateConfig(ConfigUpdate {
+            reject_overcommitted: Some(true),
+            ..Default::default()
+        });
+        let contract_info = mock_info(MOCK_CONTRACT_ADDR, &[]);
+        execute(deps.as_mut(), mock_env(), contract_info, update).unwrap();
 
-        // Propose
-        let bank_msg = BankMsg::Send {
-            to_address: SOMEBODY.into(),
-            amount: vec![coin(1, "BTC")],
-        };
-        let msgs = vec![CosmosMsg::Bank(bank_msg)];
-        let proposal = ExecuteMsg::Propose {
+        let payout = |amount: u128, denom: &str| ExecuteMsg::Propose {
             title: "Pay somebody".to_string(),
             description: "Do I pay her?".to_string(),
-            msgs: msgs.clone(),
+            msgs: vec![BankMsg::Send {
+                to_address: SOMEBODY.into(),
+                amount: vec![coin(amount, denom)],
+            }
+            .into()],
+            earliest: None,
             latest: None,
         };
-        let res = execute(deps.as_mut(), mock_env(), info, proposal).unwrap();
-
-        // Get the proposal id from the logs
+        let info = mock_info(VOTER1, &[]);
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            payout(600, "ujuno"),
+        )
+        .unwrap();
         let proposal_id: u64 = res.attributes[2].value.parse().unwrap();
 
-        let closing = ExecuteMsg::Close { proposal_id };
+        // Open proposals commit what they send
+        let res = query_treasury(deps.as_ref(), mock_env()).unwrap();
+        assert_eq!(
+            res,
+            TreasuryResponse {
+                balances: vec![coin(1000, "ujuno")],
+                committed: vec![coin(600, "ujuno")],
+                deposits: vec![],
+                uncommitted: vec![coin(400, "ujuno")],
+            }
+        );
 
-        // Anybody can close
-        let info = mock_info(SOMEBODY, &[]);
+        // Proposals cannot send more than is uncommitted
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            payout(401, "ujuno"),
+        );
+        assert_eq!(
+            err.unwrap_err(),
+            ContractError::InsufficientFunds {
+                denom: "ujuno".to_string()
+            }
+        );
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), payout(1, "uatom"));
+        assert_eq!(
+            err.unwrap_err(),
+            ContractError::InsufficientFunds {
+                denom: "uatom".to_string()
+            }
+        );
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            payout(400, "ujuno"),
+        )
+        .unwrap();
+        let expiring_id: u64 = res.attributes[2].value.parse().unwrap();
 
-        // Non-expired proposals cannot be closed
-        let err = execute(deps.as_mut(), mock_env(), info, closing).unwrap_err();
-        assert_eq!(err, ContractError::NotExpired {});
+        // Passed proposals stay committed until they are executed
+        let vote = ExecuteMsg::Vote {
+            proposal_id,
+            vote: Vote::Yes,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info(VOTER2, &[]), vote).unwrap();
+        let res = query_treasury(deps.as_ref(), mock_env()).unwrap();
+        assert_eq!(res.committed, vec![coin(1000, "ujuno")]);
+        assert_eq!(res.uncommitted, vec![coin(0, "ujuno")]);
+        let execution = ExecuteMsg::Execute { proposal_id };
+        execute(deps.as_mut(), mock_env(), info, execution).unwrap();
+        let res = query_treasury(deps.as_ref(), mock_env()).unwrap();
+        assert_eq!(res.committed, vec![coin(400, "ujuno")]);
+
+        // Expired proposals no longer commit anything once they are closed
+        let env = mock_env_height(10);
+        let res = query_treasury(deps.as_ref(), env.clone()).unwrap();
+        assert_eq!(res.committed, vec![coin(400, "ujuno")]);
+        let closing = ExecuteMsg::Close {
+            proposal_id: expiring_id,
+        };
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info(SOMEBODY, &[]),
+            closing,
+        )
+        .unwrap();
+        let res = query_treasury(deps.as_ref(), env).unwrap();
+        assert_eq!(res.committed, vec![]);
+        assert_eq!(res.uncommitted, vec![coin(1000, "ujuno")]);
+    }
+
+    #[test]
+    fn test_treasury_holds_deposits() {
+        // the balance includes the deposit paid with the proposal
+        let mut deps = mock_dependencies(&[coin(1010, "ujuno")]);
 
-        // Expired proposals can be closed
         let info = mock_info(OWNER, &[]);
+        let instantiate_msg = InstantiateMsg {
+            deposit: Some(DepositInfo {
+                amount: coin(10, "ujuno"),
+                refund_failed: false,
+            }),
+            reject_overcommitted: true,
+            ..init_msg(
+                vec![voter(OWNER, 1), voter(VOTER3, 3)],
+                Threshold::AbsoluteCount { weight: 3 },
+                Duration::Height(10),
+            )
+        };
+        instantiate(deps.as_mut(), mock_env(), info, instantiate_msg).unwrap();
 
-        let proposal = ExecuteMsg::Propose {
-            title: "(Try to) pay somebody".to_string(),
-            description: "Pay somebody after time?".to_string(),
-            msgs,
-            latest: Some(Expiration::AtHeight(123456)),
+        let payout = |amount: u128| ExecuteMsg::Propose {
+            title: "Pay somebody".to_string(),
+            description: "Do I pay her?".to_string(),
+            msgs: vec![BankMsg::Send {
+                to_address: SOMEBODY.into(),
+                amount: vec![coin(amount, "ujuno")],
+            }
+            .into()],
+            earliest: None,
+            latest: None,
         };
-        let res = execute(deps.as_mut(), mock_env(), info.clone(), proposal).unwrap();
 
-        // Get the proposal id from the logs
+        let proposer = mock_info(OWNER, &[coin(10, "ujuno")]);
+        let res = execute(deps.as_mut(), mock_env(), proposer.clone(), payout(1000)).unwrap();
         let proposal_id: u64 = res.attributes[2].value.parse().unwrap();
+        let res = query_treasury(deps.as_ref(), mock_env()).unwrap();
+        assert_eq!(
+            res,
+            TreasuryResponse {
+                balances: vec![coin(1010, "ujuno")],
+                committed: vec![coin(1000, "ujuno")],
+                deposits: vec![coin(10, "ujuno")],
+                uncommitted: vec![coin(0, "ujuno")],
+            }
+        );
 
-        let closing = ExecuteMsg::Close { proposal_id };
-
-        // Close expired works
-        let env = mock_env_height(1234567);
-        let res = execute(
+        // Allowances and modules cannot spend committed funds either
+        let contract_info = mock_info(MOCK_CONTRACT_ADDR, &[]);
+        let set_allowance = ExecuteMsg::SetAllowance {
+            spender: VOTER1.into(),
+            amount: coin(100, "ujuno"),
+            reset_period: Duration::Height(10),
+        };
+        execute(
             deps.as_mut(),
-            env,
-            mock_info(SOMEBODY, &[]),
-            closing.clone(),
+            mock_env(),
+            contract_info.clone(),
+            set_allowance,
         )
         .unwrap();
-
-        // Verify
+        let spend = ExecuteMsg::SpendAllowance {
+            to: SOMEBODY.into(),
+            amount: coin(1, "ujuno"),
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info(VOTER1, &[]), spend).unwrap_err();
         assert_eq!(
-            res,
-            Response::new()
-                .add_attribute("action", "close")
-                .add_attribute("sender", SOMEBODY)
-                .add_attribute("proposal_id", proposal_id.to_string())
+            err,
+            ContractError::InsufficientFunds {
+                denom: "ujuno".to_string()
+            }
+        );
+        let module = "payroll";
+        let enable = ExecuteMsg::EnableModule {
+            module: module.into(),
+        };
+        execute(deps.as_mut(), mock_env(), contract_info, enable).unwrap();
+        let module_execution = ExecuteMsg::ExecuteFromModule {
+            msgs: vec![BankMsg::Send {
+                to_address: SOMEBODY.into(),
+                amount: vec![coin(1, "ujuno")],
+            }
+            .into()],
+        };
+        let info = mock_info(module, &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, module_execution).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::InsufficientFunds {
+                denom: "ujuno".to_string()
+            }
         );
 
-        // Trying to close it again fails
-        let err = execute(deps.as_mut(), mock_env(), info, closing).unwrap_err();
-        assert_eq!(err, ContractError::WrongCloseStatus {});
+        // Executing releases both the commitment and the deposit
+        let vote = ExecuteMsg::Vote {
+            proposal_id,
+            vote: Vote::Yes,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info(VOTER3, &[]), vote).unwrap();
+        let execution = ExecuteMsg::Execute { proposal_id };
+        execute(deps.as_mut(), mock_env(), mock_info(OWNER, &[]), execution).unwrap();
+        let res = query_treasury(deps.as_ref(), mock_env()).unwrap();
+        assert_eq!(res.committed, vec![]);
+        assert_eq!(res.deposits, vec![]);
+
+        // The deposit paid along cannot be committed by the proposal
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, vec![coin(10, "ujuno")]);
+        let err = execute(deps.as_mut(), mock_env(), proposer, payout(1)).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::InsufficientFunds {
+                denom: "ujuno".to_string()
+            }
+        );
     }
 }
@@ -0,0 +1,44 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// The subset of the cw4 group queries this contract relies on
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Cw4QueryMsg {
+    /// Returns TotalWeightResponse
+    TotalWeight {},
+    /// Returns MemberListResponse
+    ListMembers {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Returns MemberResponse
+    Member {
+        addr: String,
+        at_height: Option<u64>,
+    },
+}
+
+/// A group member has a weight associated with them.
+/// This may all be equal, or may have meaning in the app that
+/// makes use of the group (eg. voting power)
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct Member {
+    pub addr: String,
+    pub weight: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct MemberListResponse {
+    pub members: Vec<Member>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct MemberResponse {
+    pub weight: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct TotalWeightResponse {
+    pub weight: u64,
+}
@@ -18,6 +18,33 @@ pub enum ContractError {
     #[error("Voter weight of zero")]
     VoterZeroWeight {},
 
+    #[error("Voters are managed by the cw4 group")]
+    GroupMembers {},
+
+    #[error("No proposers on the allowlist")]
+    NoProposers {},
+
+    #[error("Proposal deposit cannot be zero")]
+    ZeroDeposit {},
+
+    #[error("Must send exactly {deposit} as proposal deposit")]
+    InvalidDeposit { deposit: String },
+
+    #[error("Funds were sent, but none are expected")]
+    UnexpectedFunds {},
+
+    #[error("Allowance amount and reset period cannot be zero")]
+    InvalidAllowance {},
+
+    #[error("Cannot spend an amount of zero")]
+    ZeroAmount {},
+
+    #[error("Allowance exceeded, only {remaining} left until it resets")]
+    AllowanceExceeded { remaining: String },
+
+    #[error("Proposal sends more {denom} than is uncommitted")]
+    InsufficientFunds { denom: String },
+
     #[error("Unauthorized")]
     Unauthorized {},
 
@@ -27,18 +54,57 @@ pub enum ContractError {
     #[error("Proposal voting period has expired")]
     Expired {},
 
+    #[error("Proposal voting period has not started yet")]
+    NotStarted {},
+
     #[error("Proposal must expire before you can close it")]
     NotExpired {},
 
     #[error("Wrong expiration option")]
     WrongExpiration {},
 
+    #[error("Execution periods must use the same unit as the voting period")]
+    WrongExecutionPeriod {},
+
     #[error("Already voted on this proposal")]
     AlreadyVoted {},
 
+    #[error("Has not voted on this proposal")]
+    NotVoted {},
+
     #[error("Proposal must have passed and not yet been executed")]
     WrongExecuteStatus {},
 
+    #[error("Sender is not allowed to execute this proposal")]
+    UnauthorizedExecutor {},
+
+    #[error("Proposal cannot be executed before its execution delay is over")]
+    Timelocked {},
+
+    #[error("Proposal can no longer be executed as its execution window is over")]
+    ExecutionExpired {},
+
+    #[error("Guard contract rejected the messages")]
+    GuardRejected {},
+
     #[error("Cannot close completed or passed proposals")]
     WrongCloseStatus {},
+
+    #[error("Given address already registered as a hook")]
+    HookAlreadyRegistered {},
+
+    #[error("Given address not registered as a hook")]
+    HookNotRegistered {},
+
+    #[error("Cannot migrate from contract {contract}")]
+    WrongContract { contract: String },
+
+    #[error("Cannot migrate from version {stored} down to {current}")]
+    Downgrade { stored: String, current: String },
+
+    #[error("Cannot migrate from version {version}")]
+    UnsupportedVersion { version: String },
+
+    #[error("Unknown reply id {id}")]
+    UnknownReplyId { id: u64 },
 }
@@ -0,0 +1,91 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{to_binary, Addr, StdResult, Storage, SubMsg, WasmMsg};
+use cw_storage_plus::Item;
+
+use crate::msg::{CustomMsg, Vote};
+use crate::query::Status;
+
+/// Hook callbacks are dispatched with this reply id, so a failing hook contract
+/// cannot block the multisig
+pub const HOOK_REPLY_ID: u64 = 0;
+
+pub const HOOKS: Item<Vec<Addr>> = Item::new("hooks");
+
+/// Sent to hook contracts when a proposal is created or its status changes.
+/// Status changes that only depend on time, like a pending proposal opening or an open one
+/// expiring, are reported by the next call that stores the proposal, e.g. a vote or `Close`
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ProposalHook {
+    NewProposal {
+        id: u64,
+        proposer: String,
+    },
+    StatusChanged {
+        id: u64,
+        old_status: Status,
+        new_status: Status,
+    },
+}
+
+/// Sent to hook contracts when a vote is cast, changed or retracted
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum VoteHook {
+    NewVote {
+        proposal_id: u64,
+        voter: String,
+        vote: Vote,
+    },
+    RetractedVote {
+        proposal_id: u64,
+        voter: String,
+    },
+}
+
+/// The execute message hook contracts must accept
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum HookExecuteMsg {
+    ProposalHook(ProposalHook),
+    VoteHook(VoteHook),
+}
+
+impl HookExecuteMsg {
+    /// Creates a callback to every registered hook contract
+    pub fn into_submsgs(self, storage: &dyn Storage) -> StdResult<Vec<SubMsg<CustomMsg>>> {
+        let hooks = HOOKS.may_load(storage)?.unwrap_or_default();
+        let msg = to_binary(&self)?;
+        Ok(hooks
+            .into_iter()
+            .map(|hook| {
+                let execute = WasmMsg::Execute {
+                    contract_addr: hook.to_string(),
+                    msg: msg.clone(),
+                    funds: vec![],
+                };
+                SubMsg::reply_on_error(execute, HOOK_REPLY_ID)
+            })
+            .collect())
+    }
+}
+
+/// Notifies the hook contracts if the status of a proposal changed
+pub fn status_changed_hooks(
+    storage: &dyn Storage,
+    id: u64,
+    old_status: Status,
+    new_status: Status,
+) -> StdResult<Vec<SubMsg<CustomMsg>>> {
+    if old_status == new_status {
+        return Ok(vec![]);
+    }
+    HookExecuteMsg::ProposalHook(ProposalHook::StatusChanged {
+        id,
+        old_status,
+        new_status,
+    })
+    .into_submsgs(storage)
+}
@@ -1,13 +1,23 @@
+#[cfg(test)]
+mod conformance;
 pub mod contract;
+mod cw4;
 mod error;
-pub mod msg;
-pub mod state;
 mod expiration;
+pub mod hooks;
+mod migrations;
+pub mod msg;
 mod query;
+pub mod state;
+#[cfg(feature = "token-factory")]
+pub mod token_factory;
 
 pub use crate::error::ContractError;
-pub use crate::msg::{Cw3ExecuteMsg, Vote};
+pub use crate::msg::{CustomMsg, Cw3ExecuteMsg, Vote};
 pub use crate::query::{
-    Cw3QueryMsg, ProposalListResponse, ProposalResponse, Status, ThresholdResponse, VoteInfo,
-    VoteListResponse, VoteResponse, VoterDetail, VoterListResponse, VoterResponse,
-};
\ No newline at end of file
+    AllowanceInfo, AllowanceListResponse, AllowanceResponse, ConfigResponse, Cw3QueryMsg,
+    ExecutionFailureResponse, ExecutionReceiptResponse, GuardQueryMsg, GuardResponse,
+    HooksResponse, ModuleListResponse, ProposalListResponse, ProposalResponse,
+    ProposalStatusResponse, ProposerListResponse, Status, ThresholdResponse, TreasuryResponse,
+    VoteInfo, VoteListResponse, VoteResponse, VoterDetail, VoterListResponse, VoterResponse,
+};
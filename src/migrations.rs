@@ -0,0 +1,136 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{Addr, CosmosMsg, StdError, StdResult, Storage};
+use cw_storage_plus::{Item, Map, U64Key};
+
+use crate::expiration::{Duration, Expiration};
+use crate::msg::{CustomMsg, Executor, Proposer, Threshold, Vote};
+use crate::query::Status;
+use crate::state::{
+    add_total, outflows, Ballot, Config, Proposal, BALLOTS, COMMITTED, COMMITTING, CONFIG,
+    PROPOSALS, PROPOSAL_COUNT, TOTAL_WEIGHT, TOTAL_WEIGHT_KEY, VOTERS,
+};
+
+/// The config of 0.1 contracts, which did not store a contract version yet
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct ConfigV0_1 {
+    pub threshold_weight: u64,
+    pub total_weight: u64,
+    pub max_voting_period: Duration,
+}
+
+/// The proposals of 0.1 contracts, which only counted yes votes
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct ProposalV0_1 {
+    pub title: String,
+    pub description: String,
+    pub expires: Expiration,
+    /// custom messages were not supported, so these parse with any `CustomMsg`
+    pub msgs: Vec<CosmosMsg<CustomMsg>>,
+    pub status: Status,
+    // voters that have already casted a vote on this proposal
+    pub voters: Vec<Addr>,
+    /// how many votes have already said yes
+    pub yes_weight: u64,
+}
+
+pub const CONFIG_V0_1: Item<ConfigV0_1> = Item::new("config");
+pub const PROPOSALS_V0_1: Map<U64Key, ProposalV0_1> = Map::new("proposals");
+
+/// Upgrades the storage of a 0.1 contract. Those only stored yes voters on the proposal,
+/// which become ballots, and did not record the proposer, so `contract` takes their place.
+/// All proposals are rewritten in the migration transaction, one at a time, so multisigs
+/// with more proposals than the gas limit of a transaction allows cannot be migrated.
+pub fn migrate_v0_1(storage: &mut dyn Storage, contract: &Addr) -> StdResult<()> {
+    let old = CONFIG_V0_1.load(storage)?;
+    let threshold = Threshold::AbsoluteCount {
+        weight: old.threshold_weight,
+    };
+    let cfg = Config {
+        threshold: threshold.clone(),
+        total_weight: old.total_weight,
+        max_voting_period: old.max_voting_period,
+        execution_delay: None,
+        execution_window: None,
+        executor: Executor::Anyone {},
+        proposer: Proposer::Anyone {},
+        deposit: None,
+        guard: None,
+        group: None,
+        track_failures: false,
+        reject_overcommitted: false,
+    };
+    CONFIG.save(storage, &cfg)?;
+    // as of height 0 like at instantiation, the voters also have no earlier history
+    TOTAL_WEIGHT.save(storage, TOTAL_WEIGHT_KEY, &cfg.total_weight, 0)?;
+
+    let count = PROPOSAL_COUNT.may_load(storage)?.unwrap_or_default();
+    for id in 1..=count {
+        let old = match PROPOSALS_V0_1.may_load(storage, id.into())? {
+            Some(old) => old,
+            None => continue,
+        };
+        // voters could not be changed before, so their current weight is the one they voted with
+        for voter in old.voters.iter() {
+            let weight = VOTERS.load(storage, voter)?;
+            let ballot = Ballot {
+                weight,
+                vote: Vote::Yes,
+            };
+            BALLOTS.save(storage, (id.into(), voter), &ballot)?;
+        }
+        let prop = Proposal {
+            proposer: contract.clone(),
+            title: old.title,
+            description: old.description,
+            earliest: None,
+            expires: old.expires,
+            msgs: old.msgs,
+            start_height: 0,
+            status: old.status,
+            yes_weight: old.yes_weight,
+            no_weight: 0,
+            abstain_weight: 0,
+            veto_weight: 0,
+            threshold: threshold.clone(),
+            total_weight: cfg.total_weight,
+            execution_delay: None,
+            execution_window: None,
+            passed_at: None,
+            deposit: None,
+        };
+        // 0.1 did not take deposits, so only what proposals send is committed
+        if COMMITTING.contains(&prop.status) {
+            for coin in outflows(&prop.msgs) {
+                add_total(storage, &COMMITTED, coin)?;
+            }
+        }
+        PROPOSALS.save(storage, id.into(), &prop)?;
+    }
+    Ok(())
+}
+
+/// Parses a version like "0.2.0" into its numeric parts, so versions can be compared
+pub fn parse_version(version: &str) -> StdResult<Vec<u64>> {
+    version
+        .split('.')
+        .map(|part| {
+            part.parse()
+                .map_err(|_| StdError::generic_err(format!("Invalid version: {}", version)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn versions_compare_numerically() {
+        assert!(parse_version("0.10.0").unwrap() > parse_version("0.9.1").unwrap());
+        assert!(parse_version("1.0.0").unwrap() > parse_version("0.2.0").unwrap());
+        assert_eq!(parse_version("0.2.0").unwrap(), vec![0, 2, 0]);
+        parse_version("v0.2.0").unwrap_err();
+    }
+}
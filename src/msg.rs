@@ -1,9 +1,11 @@
 use schemars::JsonSchema;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use std::fmt;
 
-use cosmwasm_std::{CosmosMsg, Empty};
+use crate::error::ContractError;
 use crate::expiration::{Duration, Expiration};
+use crate::query::{Cw3QueryMsg, ThresholdResponse};
+use cosmwasm_std::{Coin, CosmosMsg, Decimal, Empty, Uint128};
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
 #[serde(rename_all = "snake_case")]
@@ -32,17 +34,247 @@ where
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
 pub struct InstantiateMsg {
+    /// the voters of the multisig, must be empty if a `group` is given
     pub voters: Vec<Voter>,
-    pub threshold_weight: u64,
+    /// a cw4 group contract to use as the source of voters instead of `voters`
+    pub group: Option<String>,
+    pub threshold: Threshold,
     pub max_voting_period: Duration,
+    /// how long a passed proposal has to wait before it can be executed
+    pub execution_delay: Option<Duration>,
+    /// how long a passed proposal can be executed once its execution delay is over
+    pub execution_window: Option<Duration>,
+    /// who may execute passed proposals
+    #[serde(default)]
+    pub executor: Executor,
+    /// who may create proposals
+    #[serde(default)]
+    pub proposer: Proposer,
+    /// the initial allowlist of proposers, used with `Proposer::Allowlist`
+    #[serde(default)]
+    pub proposers: Vec<String>,
+    /// the deposit required to create a proposal, if any
+    pub deposit: Option<DepositInfo>,
+    /// a contract that must approve all messages before they are dispatched, if any
+    pub guard: Option<String>,
+    /// whether failing proposals are recorded as `ExecutionFailed` instead of reverting
+    #[serde(default)]
+    pub track_failures: bool,
+    /// whether proposals sending more than the uncommitted balance are rejected
+    #[serde(default)]
+    pub reject_overcommitted: bool,
+}
+
+/// The changes made by `ExecuteMsg::UpdateConfig`.
+/// Omitted fields are left unchanged, an explicit `null` removes an optional setting.
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, JsonSchema, Debug)]
+pub struct ConfigUpdate {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub threshold: Option<Threshold>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_voting_period: Option<Duration>,
+    #[serde(
+        default,
+        deserialize_with = "set_or_clear",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub execution_delay: Option<Option<Duration>>,
+    #[serde(
+        default,
+        deserialize_with = "set_or_clear",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub execution_window: Option<Option<Duration>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub executor: Option<Executor>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proposer: Option<Proposer>,
+    #[serde(
+        default,
+        deserialize_with = "set_or_clear",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub deposit: Option<Option<DepositInfo>>,
+    #[serde(
+        default,
+        deserialize_with = "set_or_clear",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub guard: Option<Option<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub track_failures: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reject_overcommitted: Option<bool>,
+}
+
+// a field that is present is a change, even if it is `null`, which clears the setting
+fn set_or_clear<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
 }
 
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct MigrateMsg {}
+
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
 pub struct Voter {
     pub addr: String,
     pub weight: u64,
 }
 
+/// This defines the different ways tallies can happen.
+/// The total_weight used for calculating success is snapshotted when the proposal is created.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Threshold {
+    /// Declares that a fixed weight of yes votes is needed to pass.
+    /// See `ThresholdResponse.AbsoluteCount` in the cw3 spec for details.
+    AbsoluteCount { weight: u64 },
+    /// Declares a percentage of the total weight that must cast yes votes in order for
+    /// a proposal to pass.
+    /// See `ThresholdResponse.AbsolutePercentage` in the cw3 spec for details.
+    AbsolutePercentage { percentage: Decimal },
+    /// In addition to a `threshold`, declares a `quorum` of the total votes that must participate
+    /// in the election in order for the vote to be considered at all.
+    /// See `ThresholdResponse.ThresholdQuorum` in the cw3 spec for details.
+    ThresholdQuorum { threshold: Decimal, quorum: Decimal },
+}
+
+impl Threshold {
+    /// Ensures the threshold can be reached by a voter set of `total_weight`
+    pub fn validate(&self, total_weight: u64) -> Result<(), ContractError> {
+        match self {
+            Threshold::AbsoluteCount { weight } => {
+                if *weight == 0 {
+                    Err(ContractError::ZeroWeight {})
+                } else if *weight > total_weight {
+                    Err(ContractError::UnreachableWeight {})
+                } else {
+                    Ok(())
+                }
+            }
+            Threshold::AbsolutePercentage { percentage } => valid_percentage(percentage),
+            Threshold::ThresholdQuorum { threshold, quorum } => {
+                valid_percentage(threshold)?;
+                valid_percentage(quorum)
+            }
+        }
+    }
+
+    /// Creates a response from the saved data, with the given `total_weight`
+    pub fn to_response(&self, total_weight: u64) -> ThresholdResponse {
+        match self.clone() {
+            Threshold::AbsoluteCount { weight } => ThresholdResponse::AbsoluteCount {
+                weight,
+                total_weight,
+            },
+            Threshold::AbsolutePercentage { percentage } => ThresholdResponse::AbsolutePercentage {
+                percentage,
+                total_weight,
+            },
+            Threshold::ThresholdQuorum { threshold, quorum } => {
+                ThresholdResponse::ThresholdQuorum {
+                    threshold,
+                    quorum,
+                    total_weight,
+                }
+            }
+        }
+    }
+}
+
+/// Asserts that the 0.0 < percent <= 1.0
+fn valid_percentage(percent: &Decimal) -> Result<(), ContractError> {
+    if percent.is_zero() {
+        Err(ContractError::ZeroWeight {})
+    } else if *percent > Decimal::one() {
+        Err(ContractError::UnreachableWeight {})
+    } else {
+        Ok(())
+    }
+}
+
+// we multiply by this when calculating needed_votes in order to round up properly
+const PRECISION_FACTOR: u128 = 1_000_000_000;
+
+// this is a helper function so Decimal works with u64 rather than Uint128
+// also, we must *round up* here, as we need 8, not 7 votes to reach 50% of 15 total
+pub(crate) fn votes_needed(weight: u64, percentage: Decimal) -> u64 {
+    let applied = percentage * Uint128::from(PRECISION_FACTOR * weight as u128);
+    // Divide by PRECISION_FACTOR, rounding up to the nearest integer
+    ((applied.u128() + PRECISION_FACTOR - 1) / PRECISION_FACTOR) as u64
+}
+
+/// Defines who is allowed to execute passed proposals
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Executor {
+    /// Any address can execute a passed proposal
+    Anyone {},
+    /// Only voters of the multisig can execute a passed proposal
+    Member {},
+    /// Only the address that created a proposal can execute it
+    Proposer {},
+}
+
+impl Default for Executor {
+    fn default() -> Self {
+        Executor::Anyone {}
+    }
+}
+
+/// Defines who is allowed to create proposals
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Proposer {
+    /// Any address can create a proposal
+    Anyone {},
+    /// Only voters of the multisig can create a proposal
+    Member {},
+    /// Only addresses on the allowlist of proposers can create a proposal
+    Allowlist {},
+}
+
+impl Default for Proposer {
+    fn default() -> Self {
+        Proposer::Anyone {}
+    }
+}
+
+/// A deposit that must be sent along with every new proposal
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct DepositInfo {
+    /// the native tokens to send in `info.funds` when proposing
+    pub amount: Coin,
+    /// whether the deposit is also refunded if the proposal is rejected,
+    /// otherwise it is kept by the multisig
+    pub refund_failed: bool,
+}
+
+impl DepositInfo {
+    /// Ensures the deposit is actually worth something
+    pub fn validate(&self) -> Result<(), ContractError> {
+        if self.amount.amount.is_zero() {
+            Err(ContractError::ZeroDeposit {})
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Ensures `funds` are exactly the required deposit
+    pub fn check_funds(&self, funds: &[Coin]) -> Result<(), ContractError> {
+        if funds == [self.amount.clone()] {
+            Ok(())
+        } else {
+            Err(ContractError::InvalidDeposit {
+                deposit: self.amount.to_string(),
+            })
+        }
+    }
+}
 
 #[derive(Serialize, Deserialize, Clone, Copy, PartialEq, JsonSchema, Debug)]
 #[serde(rename_all = "lowercase")]
@@ -58,30 +290,136 @@ pub enum Vote {
     Veto,
 }
 
-// TODO: add some T variants? Maybe good enough as fixed Empty for now
+/// The custom message type of proposals, which depends on the chain the contract is built for
+#[cfg(not(feature = "token-factory"))]
+pub type CustomMsg = Empty;
+/// The custom message type of proposals, which depends on the chain the contract is built for
+#[cfg(feature = "token-factory")]
+pub type CustomMsg = crate::token_factory::TokenFactoryMsg;
+
+/// The messages accepted by `execute`. This is a superset of `Cw3ExecuteMsg`,
+/// so any cw3 execute message is valid with the same meaning.
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
-pub enum ExecuteMsg {
+pub enum ExecuteMsg<T = Empty>
+where
+    T: Clone + fmt::Debug + PartialEq + JsonSchema,
+{
     Propose {
         title: String,
         description: String,
-        msgs: Vec<CosmosMsg<Empty>>,
-        // note: we ignore API-spec'd earliest if passed, always opens immediately
+        msgs: Vec<CosmosMsg<T>>,
+        /// voting only opens at this point, the proposal is pending until then
+        earliest: Option<Expiration>,
         latest: Option<Expiration>,
     },
     Vote {
         proposal_id: u64,
         vote: Vote,
     },
+    /// Withdraws the sender's vote while the proposal is still open
+    Retract {
+        proposal_id: u64,
+    },
     Execute {
         proposal_id: u64,
     },
     Close {
         proposal_id: u64,
     },
+    /// Adds or removes voters. An added voter that already exists has their weight updated.
+    /// Can only be called by the multisig itself, i.e. by executing a passed proposal.
+    UpdateMembers {
+        add: Vec<Voter>,
+        remove: Vec<String>,
+    },
+    /// Changes the voting rules used for new proposals, fields left out are unchanged.
+    /// Can only be called by the multisig itself, i.e. by executing a passed proposal.
+    UpdateConfig(ConfigUpdate),
+    /// Adds or removes addresses from the allowlist of proposers.
+    /// Can only be called by the multisig itself, i.e. by executing a passed proposal.
+    UpdateProposers {
+        add: Vec<String>,
+        remove: Vec<String>,
+    },
+    /// Allows the module contract to execute messages from the multisig without a vote.
+    /// Can only be called by the multisig itself, i.e. by executing a passed proposal.
+    EnableModule {
+        module: String,
+    },
+    /// Revokes a module's permission to execute messages.
+    /// Can only be called by the multisig itself, i.e. by executing a passed proposal.
+    DisableModule {
+        module: String,
+    },
+    /// Dispatches messages from the multisig. Can only be called by enabled modules.
+    ExecuteFromModule {
+        msgs: Vec<CosmosMsg<T>>,
+    },
+    /// Dispatches the messages of the proposal that is being executed with failure tracking.
+    /// Can only be called by the multisig itself while executing that proposal.
+    DispatchProposal {
+        proposal_id: u64,
+    },
+    /// Registers a contract to be notified about proposals and votes.
+    /// Can only be called by the multisig itself, i.e. by executing a passed proposal.
+    AddHook {
+        addr: String,
+    },
+    /// Stops notifying a contract about proposals and votes.
+    /// Can only be called by the multisig itself, i.e. by executing a passed proposal.
+    RemoveHook {
+        addr: String,
+    },
+    /// Grants `spender` a recurring allowance to spend `amount` without a proposal,
+    /// replacing any allowance they have for that denom.
+    /// Can only be called by the multisig itself, i.e. by executing a passed proposal.
+    SetAllowance {
+        spender: String,
+        amount: Coin,
+        reset_period: Duration,
+    },
+    /// Revokes the allowance of `spender` for `denom`.
+    /// Can only be called by the multisig itself, i.e. by executing a passed proposal.
+    RemoveAllowance {
+        spender: String,
+        denom: String,
+    },
+    /// Sends `amount` from the multisig to `to`, within the sender's allowance for that denom
+    SpendAllowance {
+        to: String,
+        amount: Coin,
+    },
+}
+
+impl<T> From<Cw3ExecuteMsg<T>> for ExecuteMsg<T>
+where
+    T: Clone + fmt::Debug + PartialEq + JsonSchema,
+{
+    fn from(msg: Cw3ExecuteMsg<T>) -> Self {
+        match msg {
+            Cw3ExecuteMsg::Propose {
+                title,
+                description,
+                msgs,
+                earliest,
+                latest,
+            } => ExecuteMsg::Propose {
+                title,
+                description,
+                msgs,
+                earliest,
+                latest,
+            },
+            Cw3ExecuteMsg::Vote { proposal_id, vote } => ExecuteMsg::Vote { proposal_id, vote },
+            Cw3ExecuteMsg::Execute { proposal_id } => ExecuteMsg::Execute { proposal_id },
+            Cw3ExecuteMsg::Close { proposal_id } => ExecuteMsg::Close { proposal_id },
+        }
+    }
 }
 
-// We can also add this as a cw3 extension
+/// The queries answered by `query`. This is a superset of `Cw3QueryMsg`,
+/// so any cw3 query is valid and returns the cw3 response.
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
@@ -114,4 +452,140 @@ pub enum QueryMsg {
         start_after: Option<String>,
         limit: Option<u32>,
     },
+    /// Returns ConfigResponse
+    Config {},
+    /// Returns ProposerListResponse
+    ListProposers {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Returns ModuleListResponse
+    ListModules {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Returns HooksResponse
+    ListHooks {},
+    /// Returns ProposalStatusResponse
+    ProposalStatus { proposal_id: u64 },
+    /// Returns ExecutionFailureResponse
+    ExecutionFailure { proposal_id: u64 },
+    /// Returns ExecutionReceiptResponse
+    ExecutionReceipt { proposal_id: u64 },
+    /// Returns AllowanceResponse
+    Allowance { spender: String, denom: String },
+    /// Returns AllowanceListResponse
+    ListAllowances {
+        spender: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Returns TreasuryResponse
+    Treasury {},
+}
+
+impl From<Cw3QueryMsg> for QueryMsg {
+    fn from(msg: Cw3QueryMsg) -> Self {
+        match msg {
+            Cw3QueryMsg::Threshold {} => QueryMsg::Threshold {},
+            Cw3QueryMsg::Proposal { proposal_id } => QueryMsg::Proposal { proposal_id },
+            Cw3QueryMsg::ListProposals { start_after, limit } => {
+                QueryMsg::ListProposals { start_after, limit }
+            }
+            Cw3QueryMsg::ReverseProposals {
+                start_before,
+                limit,
+            } => QueryMsg::ReverseProposals {
+                start_before,
+                limit,
+            },
+            Cw3QueryMsg::Vote { proposal_id, voter } => QueryMsg::Vote { proposal_id, voter },
+            Cw3QueryMsg::ListVotes {
+                proposal_id,
+                start_after,
+                limit,
+            } => QueryMsg::ListVotes {
+                proposal_id,
+                start_after,
+                limit,
+            },
+            Cw3QueryMsg::Voter { address } => QueryMsg::Voter { address },
+            Cw3QueryMsg::ListVoters { start_after, limit } => {
+                QueryMsg::ListVoters { start_after, limit }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn votes_needed_rounds_properly() {
+        // round up right below 1
+        assert_eq!(1, votes_needed(3, Decimal::permille(333)));
+        // round up right over 1
+        assert_eq!(2, votes_needed(3, Decimal::permille(334)));
+        assert_eq!(11, votes_needed(30, Decimal::permille(334)));
+
+        // exact matches don't round
+        assert_eq!(17, votes_needed(34, Decimal::percent(50)));
+        assert_eq!(12, votes_needed(48, Decimal::percent(25)));
+    }
+
+    #[test]
+    fn validate_threshold_works() {
+        // AbsoluteCount must be reachable and non-zero
+        let err = Threshold::AbsoluteCount { weight: 0 }
+            .validate(5)
+            .unwrap_err();
+        assert_eq!(err, ContractError::ZeroWeight {});
+        let err = Threshold::AbsoluteCount { weight: 6 }
+            .validate(5)
+            .unwrap_err();
+        assert_eq!(err, ContractError::UnreachableWeight {});
+        Threshold::AbsoluteCount { weight: 5 }.validate(5).unwrap();
+
+        // AbsolutePercentage must be in (0, 1]
+        let err = Threshold::AbsolutePercentage {
+            percentage: Decimal::zero(),
+        }
+        .validate(5)
+        .unwrap_err();
+        assert_eq!(err, ContractError::ZeroWeight {});
+        let err = Threshold::AbsolutePercentage {
+            percentage: Decimal::percent(101),
+        }
+        .validate(5)
+        .unwrap_err();
+        assert_eq!(err, ContractError::UnreachableWeight {});
+        Threshold::AbsolutePercentage {
+            percentage: Decimal::one(),
+        }
+        .validate(5)
+        .unwrap();
+
+        // ThresholdQuorum needs both to be valid percentages
+        let err = Threshold::ThresholdQuorum {
+            threshold: Decimal::percent(50),
+            quorum: Decimal::zero(),
+        }
+        .validate(5)
+        .unwrap_err();
+        assert_eq!(err, ContractError::ZeroWeight {});
+        let err = Threshold::ThresholdQuorum {
+            threshold: Decimal::percent(150),
+            quorum: Decimal::percent(50),
+        }
+        .validate(5)
+        .unwrap_err();
+        assert_eq!(err, ContractError::UnreachableWeight {});
+        Threshold::ThresholdQuorum {
+            threshold: Decimal::percent(50),
+            quorum: Decimal::percent(30),
+        }
+        .validate(5)
+        .unwrap();
+    }
 }
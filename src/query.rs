@@ -2,10 +2,10 @@ use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::fmt;
 
-use cosmwasm_std::{CosmosMsg, Empty};
-use crate::expiration::Expiration;
+use crate::expiration::{Duration, Expiration};
+use cosmwasm_std::{Addr, Coin, CosmosMsg, Decimal, Empty, SubMsgExecutionResponse};
 
-use crate::msg::Vote;
+use crate::msg::{DepositInfo, Executor, Proposer, Vote};
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
 #[serde(rename_all = "snake_case")]
@@ -68,6 +68,47 @@ pub enum ThresholdResponse {
     ///
     /// A proposal of this type can pass early as soon as the needed weight of yes votes has been cast.
     AbsoluteCount { weight: u64, total_weight: u64 },
+    /// Declares a percentage of the total weight that must cast Yes votes in order for
+    /// a proposal to pass.
+    ///
+    /// This is useful for similar circumstances as `AbsoluteCount`, where we have a relatively
+    /// small set of voters, and participation is required.
+    /// It is understood that if the voting set (group) changes between different proposals that
+    /// refer to the same group, each proposal will work with a different set of voter weights
+    /// (the ones snapshotted at proposal creation), and the passing weight for each proposal
+    /// will be computed based on the absolute percentage, times the total weights of the members
+    /// at the time of each proposal creation.
+    ///
+    /// Example: we set `percentage` to 51%. Proposal 1 starts when there is a `total_weight` of 5.
+    /// This will require 3 weight of Yes votes in order to pass. Later, the Proposal 2 starts but the
+    /// `total_weight` of the group has increased to 9. That proposal will then automatically
+    /// require 5 Yes of 9 to pass, rather than 3 yes of 9 as would be the case with `AbsoluteCount`.
+    AbsolutePercentage {
+        percentage: Decimal,
+        total_weight: u64,
+    },
+    /// In addition to a `threshold`, declares a `quorum` of the total votes that must participate
+    /// in the election in order for the vote to be considered at all. Within the votes that
+    /// were cast, it requires `threshold` votes in favor. That is calculated by ignoring
+    /// the Abstain votes (they count towards `quorum`, but do not influence `threshold`).
+    /// That is, we calculate `Yes / (Yes + No + Veto)` and compare it with `threshold` to consider
+    /// if the proposal was passed.
+    ///
+    /// It is rather difficult for a proposal of this type to pass early. That can only happen if
+    /// the required quorum has been already met, and there are already enough Yes votes for the
+    /// proposal to pass.
+    ///
+    /// 30% Yes votes, 10% No votes, and 20% Abstain would pass early if quorum <= 60%
+    /// (who has cast votes) and if the threshold is <= 37.5% (the remaining 40% voting
+    /// no => 30% yes + 50% no). Otherwise, you must wait for the voting period to end.
+    ///
+    /// 4% Yes votes, 2% No votes, and 4% Abstain would only pass if quorum <= 10%
+    /// and threshold <= 66.6% (Yes / (Yes + No)) once voting ends.
+    ThresholdQuorum {
+        threshold: Decimal,
+        quorum: Decimal,
+        total_weight: u64,
+    },
 }
 
 /// Note, if you are storing custom messages in the proposal,
@@ -82,6 +123,7 @@ where
     pub title: String,
     pub description: String,
     pub msgs: Vec<CosmosMsg<T>>,
+    /// the status as cw3 knows it, see `Status::to_cw3`
     pub status: Status,
     pub expires: Expiration,
     /// This is the threshold that is applied to this proposal. Both the rules of the voting contract,
@@ -104,11 +146,31 @@ pub enum Status {
     Passed = 4,
     /// voting is over it passed, and the proposal was executed
     Executed = 5,
+    /// voting is over and it did pass, but it was not executed within its execution window
+    ExecutionExpired = 6,
+    /// the proposal was executed, but one of its messages failed, so it can be executed again
+    ExecutionFailed = 7,
+}
+
+impl Status {
+    /// returns the closest status cw3 knows, as its clients cannot parse the ones added here:
+    /// failed executions can be executed again like passed proposals,
+    /// expired executions can only be closed like rejected proposals
+    pub fn to_cw3(self) -> Status {
+        match self {
+            Status::ExecutionFailed => Status::Passed,
+            Status::ExecutionExpired => Status::Rejected,
+            status => status,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
-pub struct ProposalListResponse {
-    pub proposals: Vec<ProposalResponse>,
+pub struct ProposalListResponse<T = Empty>
+where
+    T: Clone + fmt::Debug + PartialEq + JsonSchema,
+{
+    pub proposals: Vec<ProposalResponse<T>>,
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
@@ -145,3 +207,107 @@ pub struct VoterDetail {
     pub addr: String,
     pub weight: u64,
 }
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct ConfigResponse {
+    pub threshold: ThresholdResponse,
+    pub max_voting_period: Duration,
+    pub execution_delay: Option<Duration>,
+    pub execution_window: Option<Duration>,
+    pub executor: Executor,
+    pub proposer: Proposer,
+    pub deposit: Option<DepositInfo>,
+    pub guard: Option<Addr>,
+    pub group: Option<Addr>,
+    pub track_failures: bool,
+    pub reject_overcommitted: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct ProposerListResponse {
+    pub proposers: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct ProposalStatusResponse {
+    /// the status of the proposal, including the ones cw3 does not know
+    pub status: Status,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct ExecutionFailureResponse {
+    /// the error of the last failed execution, if any
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct ExecutionReceiptResponse {
+    /// the events and data returned by each message of the proposal, in message order
+    pub receipts: Vec<SubMsgExecutionResponse>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct AllowanceResponse {
+    pub allowance: Option<AllowanceInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct AllowanceListResponse {
+    pub allowances: Vec<AllowanceInfo>,
+}
+
+/// An allowance as of the queried block
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct AllowanceInfo {
+    /// how much can be spent per period
+    pub amount: Coin,
+    /// how much can still be spent in the current period
+    pub remaining: Coin,
+    pub reset_period: Duration,
+    /// the end of the current period, after which `remaining` is back to `amount`
+    pub resets: Expiration,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct TreasuryResponse {
+    /// the native tokens held by the multisig
+    pub balances: Vec<Coin>,
+    /// the native tokens proposals that can still be executed would send, per denom.
+    /// Expired proposals only stop counting once they are closed
+    pub committed: Vec<Coin>,
+    /// the proposal deposits held until their proposals are completed, per denom
+    pub deposits: Vec<Coin>,
+    /// the part of the balances that is neither committed nor held as a deposit,
+    /// i.e. what can really be spent
+    pub uncommitted: Vec<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct ModuleListResponse {
+    pub modules: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct HooksResponse {
+    pub hooks: Vec<String>,
+}
+
+/// The query a guard contract must answer before the multisig dispatches any messages
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum GuardQueryMsg<T = Empty>
+where
+    T: Clone + fmt::Debug + PartialEq + JsonSchema,
+{
+    /// Returns GuardResponse
+    CheckMsgs {
+        /// the address triggering the execution
+        sender: String,
+        msgs: Vec<CosmosMsg<T>>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct GuardResponse {
+    pub allowed: bool,
+}
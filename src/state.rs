@@ -1,47 +1,365 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::convert::TryInto;
+use std::fmt;
 
-use cosmwasm_std::{Addr, BlockInfo, CosmosMsg, Empty, StdError, StdResult, Storage};
+use cosmwasm_std::{
+    Addr, BankMsg, BlockInfo, Coin, CosmosMsg, Deps, Empty, StdError, StdResult, Storage,
+    SubMsgExecutionResponse, Timestamp, Uint128,
+};
 
+use crate::cw4::{Cw4QueryMsg, MemberResponse, TotalWeightResponse};
 use crate::expiration::{Duration, Expiration};
+use crate::msg::{votes_needed, CustomMsg, DepositInfo, Executor, Proposer, Threshold, Vote};
 use crate::query::Status;
-use cw_storage_plus::{Item, Map, U64Key};
+use cw_storage_plus::{Item, Map, SnapshotMap, Strategy, U64Key};
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
 pub struct Config {
-    pub threshold_weight: u64,
+    pub threshold: Threshold,
+    /// the total weight of all voters, unused if a `group` is set
     pub total_weight: u64,
     pub max_voting_period: Duration,
+    /// how long a passed proposal has to wait before it can be executed
+    pub execution_delay: Option<Duration>,
+    /// how long a passed proposal can be executed once its execution delay is over
+    pub execution_window: Option<Duration>,
+    /// who may execute passed proposals
+    pub executor: Executor,
+    /// who may create proposals
+    pub proposer: Proposer,
+    /// the deposit required to create a proposal, if any
+    pub deposit: Option<DepositInfo>,
+    /// a contract that must approve all messages before they are dispatched, if any
+    pub guard: Option<Addr>,
+    /// the cw4 group contract voters are taken from, instead of `VOTERS`
+    pub group: Option<Addr>,
+    /// whether failing proposals are recorded as `ExecutionFailed` instead of reverting
+    #[serde(default)]
+    pub track_failures: bool,
+    /// whether proposals sending more than the uncommitted balance are rejected
+    #[serde(default)]
+    pub reject_overcommitted: bool,
+}
+
+impl Config {
+    /// returns the weight of a voter, `None` if they cannot vote.
+    /// The weight is taken at the start of `height`, or the current one if `None`
+    pub fn voter_weight(
+        &self,
+        deps: Deps,
+        addr: &Addr,
+        height: Option<u64>,
+    ) -> StdResult<Option<u64>> {
+        let weight = match &self.group {
+            Some(group) => {
+                let query = Cw4QueryMsg::Member {
+                    addr: addr.to_string(),
+                    at_height: height,
+                };
+                let res: MemberResponse = deps.querier.query_wasm_smart(group, &query)?;
+                res.weight
+            }
+            None => match height {
+                Some(height) => VOTERS.may_load_at_height(deps.storage, addr, height)?,
+                None => VOTERS.may_load(deps.storage, addr)?,
+            },
+        };
+        // group members can have a weight of zero, but they cannot vote
+        Ok(weight.filter(|weight| *weight > 0))
+    }
+
+    /// returns the total weight of all voters at the start of `height`, like `voter_weight`.
+    /// The total weight of a group has no history, so its current one is used
+    pub fn total_weight_at(&self, deps: Deps, height: u64) -> StdResult<u64> {
+        match &self.group {
+            Some(_) => self.current_total_weight(deps),
+            None => Ok(TOTAL_WEIGHT
+                .may_load_at_height(deps.storage, TOTAL_WEIGHT_KEY, height)?
+                .unwrap_or_default()),
+        }
+    }
+
+    /// returns the current total weight of all voters
+    pub fn current_total_weight(&self, deps: Deps) -> StdResult<u64> {
+        match &self.group {
+            Some(group) => {
+                let res: TotalWeightResponse = deps
+                    .querier
+                    .query_wasm_smart(group, &Cw4QueryMsg::TotalWeight {})?;
+                Ok(res.weight)
+            }
+            None => Ok(self.total_weight),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
-pub struct Proposal {
+pub struct Proposal<T = Empty>
+where
+    T: Clone + fmt::Debug + PartialEq + JsonSchema,
+{
+    pub proposer: Addr,
     pub title: String,
     pub description: String,
+    /// voting starts at this point, the proposal is pending until then
+    pub earliest: Option<Expiration>,
     pub expires: Expiration,
-    pub msgs: Vec<CosmosMsg<Empty>>,
+    pub msgs: Vec<CosmosMsg<T>>,
+    /// the height at which the proposal was created, voter weights are taken from this point
+    pub start_height: u64,
     pub status: Status,
-    // voters that have already casted a vote on this proposal
-    pub voters: Vec<Addr>,
     /// how many votes have already said yes
     pub yes_weight: u64,
+    /// how many votes have already said no
+    pub no_weight: u64,
+    /// how many votes have already abstained
+    pub abstain_weight: u64,
+    /// how many votes have already vetoed
+    pub veto_weight: u64,
+    /// the threshold in force when the proposal was created
+    pub threshold: Threshold,
+    /// the total weight of all voters when the proposal was created
+    pub total_weight: u64,
+    /// the execution delay in force when the proposal was created
+    pub execution_delay: Option<Duration>,
+    /// the execution window in force when the proposal was created
+    pub execution_window: Option<Duration>,
+    /// the block at which the proposal passed, if it passed before its voting period ended
+    pub passed_at: Option<PassedAt>,
+    /// the deposit paid by the proposer, as long as it is held by the multisig
+    pub deposit: Option<DepositInfo>,
+}
+
+/// The height and time at which a proposal passed
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, JsonSchema, Debug)]
+pub struct PassedAt {
+    pub height: u64,
+    pub time: Timestamp,
 }
 
-impl Proposal {
-    pub fn current_status(&self, block: &BlockInfo, threshold_weight: &u64) -> Status {
+impl PassedAt {
+    /// Create an expiration for Duration after passing
+    pub fn after(&self, duration: Duration) -> Expiration {
+        match duration {
+            Duration::Height(h) => Expiration::AtHeight(self.height + h),
+            Duration::Time(t) => Expiration::AtTime(self.time.plus_seconds(t)),
+        }
+    }
+}
+
+impl<T> Proposal<T>
+where
+    T: Clone + fmt::Debug + PartialEq + JsonSchema,
+{
+    pub fn current_status(&self, block: &BlockInfo) -> Status {
         let mut status = self.status;
 
-        // if open, check if voting is passed or timed out
-        if status == Status::Open && self.yes_weight >= *threshold_weight {
+        // if pending, check if voting has started
+        if status == Status::Pending && self.has_started(block) {
+            status = Status::Open;
+        }
+        // if open, check if voting is passed, can no longer pass or timed out
+        if status == Status::Open && self.is_passed(block) {
             status = Status::Passed;
         }
-        if status == Status::Open && self.expires.is_expired(block) {
+        if status == Status::Open && (self.is_unreachable() || self.expires.is_expired(block)) {
             status = Status::Rejected;
         }
+        // if passed, check if it can still be executed
+        // (execution periods share the unit of the voting period, so computing it cannot fail)
+        if matches!(status, Status::Passed | Status::ExecutionFailed) {
+            if let Ok(Some(deadline)) = self.execution_deadline() {
+                if deadline.is_expired(block) {
+                    status = Status::ExecutionExpired;
+                }
+            }
+        }
 
         status
     }
+
+    pub fn set_passed(&mut self, block: &BlockInfo) {
+        self.status = Status::Passed;
+        self.passed_at = Some(PassedAt {
+            height: block.height,
+            time: block.time,
+        });
+    }
+
+    /// returns the point at which a passed proposal can be executed, if it has an execution delay
+    pub fn timelock(&self) -> StdResult<Option<Expiration>> {
+        self.execution_delay
+            .map(|delay| self.after_passing(delay))
+            .transpose()
+    }
+
+    /// returns the point at which a passed proposal can no longer be executed, if it has an
+    /// execution window
+    pub fn execution_deadline(&self) -> StdResult<Option<Expiration>> {
+        let window = match self.execution_window {
+            Some(window) => window,
+            None => return Ok(None),
+        };
+        let period = match self.execution_delay {
+            Some(delay) => (delay + window)?,
+            None => window,
+        };
+        self.after_passing(period).map(Some)
+    }
+
+    /// returns the point `duration` after the proposal passed
+    fn after_passing(&self, duration: Duration) -> StdResult<Expiration> {
+        match self.passed_at {
+            Some(passed_at) => Ok(passed_at.after(duration)),
+            // proposals that were not voted through passed without a vote being cast:
+            // pending ones with enough weight from the proposer's ballot pass once voting starts,
+            // the others only once voting is over (see ThresholdQuorum)
+            None => match self.earliest {
+                Some(earliest) if self.meets_threshold(false) => earliest + duration,
+                _ => self.expires + duration,
+            },
+        }
+    }
+
+    /// returns true once voting on the proposal is possible
+    pub fn has_started(&self, block: &BlockInfo) -> bool {
+        match self.earliest {
+            Some(start) => start.is_expired(block),
+            None => true,
+        }
+    }
+
+    /// returns true if the votes cast so far satisfy the threshold
+    pub fn is_passed(&self, block: &BlockInfo) -> bool {
+        self.meets_threshold(self.expires.is_expired(block))
+    }
+
+    /// returns true if the votes cast so far satisfy the threshold, before or after voting is over
+    fn meets_threshold(&self, expired: bool) -> bool {
+        match self.threshold {
+            Threshold::AbsoluteCount { weight } => self.yes_weight >= weight,
+            Threshold::AbsolutePercentage { percentage } => {
+                self.yes_weight >= votes_needed(self.total_weight, percentage)
+            }
+            Threshold::ThresholdQuorum { threshold, quorum } => {
+                // we always require the quorum
+                if self.cast_weight() < votes_needed(self.total_weight, quorum) {
+                    return false;
+                }
+                if expired {
+                    // once expired, only the votes that were actually cast are compared
+                    let opinions = self.cast_weight() - self.abstain_weight;
+                    self.yes_weight >= votes_needed(opinions, threshold)
+                } else {
+                    // before that, we must assume all missing votes will be cast against
+                    let possible_opinions = self.total_weight.saturating_sub(self.abstain_weight);
+                    self.yes_weight >= votes_needed(possible_opinions, threshold)
+                }
+            }
+        }
+    }
+
+    /// returns true if the threshold cannot be reached anymore,
+    /// even if all of the weight that has not voted yet votes yes
+    pub fn is_unreachable(&self) -> bool {
+        let remaining_weight = self.total_weight.saturating_sub(self.cast_weight());
+        let max_yes_weight = self.yes_weight + remaining_weight;
+        match self.threshold {
+            Threshold::AbsoluteCount { weight } => max_yes_weight < weight,
+            Threshold::AbsolutePercentage { percentage } => {
+                max_yes_weight < votes_needed(self.total_weight, percentage)
+            }
+            Threshold::ThresholdQuorum { threshold, .. } => {
+                // the yes ratio is highest if all remaining weight votes yes,
+                // and quorum is always reached in that case
+                let possible_opinions = self.total_weight.saturating_sub(self.abstain_weight);
+                max_yes_weight < votes_needed(possible_opinions, threshold)
+            }
+        }
+    }
+
+    /// the total weight of all votes cast so far
+    pub fn cast_weight(&self) -> u64 {
+        self.yes_weight + self.no_weight + self.abstain_weight + self.veto_weight
+    }
+
+    pub fn add_vote(&mut self, vote: Vote, weight: u64) {
+        *self.tally_mut(vote) += weight;
+    }
+
+    pub fn remove_vote(&mut self, vote: Vote, weight: u64) {
+        *self.tally_mut(vote) -= weight;
+    }
+
+    fn tally_mut(&mut self, vote: Vote) -> &mut u64 {
+        match vote {
+            Vote::Yes => &mut self.yes_weight,
+            Vote::No => &mut self.no_weight,
+            Vote::Abstain => &mut self.abstain_weight,
+            Vote::Veto => &mut self.veto_weight,
+        }
+    }
+}
+
+/// A vote cast on a proposal, along with the voter's weight at the time of voting
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct Ballot {
+    pub weight: u64,
+    pub vote: Vote,
+}
+
+/// A recurring allowance to spend one denom from the multisig without a proposal
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct Allowance {
+    /// how much can be spent per period
+    pub amount: Uint128,
+    /// how much has been spent in the current period
+    pub spent: Uint128,
+    /// how long each period lasts
+    pub reset_period: Duration,
+    /// the end of the current period
+    pub resets: Expiration,
+}
+
+impl Allowance {
+    /// Create an allowance whose first period starts at `block`
+    pub fn new(amount: Uint128, reset_period: Duration, block: &BlockInfo) -> Self {
+        Allowance {
+            amount,
+            spent: Uint128::zero(),
+            reset_period,
+            resets: reset_period.after(block),
+        }
+    }
+
+    /// returns the allowance as of `block`, moving on to the period `block` falls into
+    /// if the current one is over
+    pub fn current(mut self, block: &BlockInfo) -> Self {
+        if !self.resets.is_expired(block) {
+            return self;
+        }
+        // periods are kept aligned to when the allowance was granted
+        self.resets = match (self.resets, self.reset_period) {
+            (Expiration::AtHeight(end), Duration::Height(period)) => {
+                let periods = (block.height - end) / period + 1;
+                Expiration::AtHeight(end + periods * period)
+            }
+            (Expiration::AtTime(end), Duration::Time(period)) => {
+                let periods = (block.time.seconds() - end.seconds()) / period + 1;
+                Expiration::AtTime(end.plus_seconds(periods * period))
+            }
+            // the end of a period is always in the unit of the period
+            _ => self.reset_period.after(block),
+        };
+        self.spent = Uint128::zero();
+        self
+    }
+
+    /// how much can still be spent in the current period
+    pub fn remaining(&self) -> Uint128 {
+        self.amount.checked_sub(self.spent).unwrap_or_default()
+    }
 }
 
 // unique items
@@ -49,8 +367,88 @@ pub const CONFIG: Item<Config> = Item::new("config");
 pub const PROPOSAL_COUNT: Item<u64> = Item::new("proposal_count");
 
 // multiple-item maps
-pub const VOTERS: Map<&Addr, u64> = Map::new("voters");
-pub const PROPOSALS: Map<U64Key, Proposal> = Map::new("proposals");
+/// the voters and their weights, with a history of changes so proposals can use the voters
+/// at their start like with a cw4 group
+pub const VOTERS: SnapshotMap<&Addr, u64> = SnapshotMap::new(
+    "voters",
+    "voters__checkpoints",
+    "voters__changelog",
+    Strategy::EveryBlock,
+);
+/// the total weight of `VOTERS`, with the same history so proposals take both from the same point
+pub const TOTAL_WEIGHT: SnapshotMap<&str, u64> = SnapshotMap::new(
+    "total_weight",
+    "total_weight__checkpoints",
+    "total_weight__changelog",
+    Strategy::EveryBlock,
+);
+/// the only key of `TOTAL_WEIGHT`, which is a map to keep its history
+pub const TOTAL_WEIGHT_KEY: &str = "total";
+pub const PROPOSERS: Map<&Addr, Empty> = Map::new("proposers");
+pub const MODULES: Map<&Addr, Empty> = Map::new("modules");
+/// the allowances per spender and denom
+pub const ALLOWANCES: Map<(&Addr, &str), Allowance> = Map::new("allowances");
+/// the error of the last failed execution per proposal
+pub const EXECUTION_FAILURES: Map<U64Key, String> = Map::new("execution_failures");
+/// the results of the messages of executed proposals, in message order
+pub const EXECUTION_RECEIPTS: Map<U64Key, Vec<SubMsgExecutionResponse>> =
+    Map::new("execution_receipts");
+/// the proposal whose messages are about to be dispatched with failure tracking
+pub const DISPATCHING: Item<u64> = Item::new("dispatching");
+pub const PROPOSALS: Map<U64Key, Proposal<CustomMsg>> = Map::new("proposals");
+pub const BALLOTS: Map<(U64Key, &Addr), Ballot> = Map::new("ballots");
+/// what proposals with a `COMMITTING` status would send from the multisig, per denom
+pub const COMMITTED: Map<&str, Uint128> = Map::new("committed");
+/// the deposits held for proposals that are not completed yet, per denom
+pub const HELD_DEPOSITS: Map<&str, Uint128> = Map::new("held_deposits");
+
+/// The stored statuses of proposals that can still be executed, so what they send is committed.
+/// Proposals stay committed until a call stores their new status, e.g. closing expired ones
+pub const COMMITTING: [Status; 4] = [
+    Status::Pending,
+    Status::Open,
+    Status::Passed,
+    Status::ExecutionFailed,
+];
+
+/// returns the coins `msgs` send from the multisig with `BankMsg::Send`
+pub fn outflows<T>(msgs: &[CosmosMsg<T>]) -> impl Iterator<Item = &Coin>
+where
+    T: Clone + fmt::Debug + PartialEq + JsonSchema,
+{
+    msgs.iter()
+        .filter_map(|msg| match msg {
+            CosmosMsg::Bank(BankMsg::Send { amount, .. }) => Some(amount),
+            _ => None,
+        })
+        .flatten()
+}
+
+/// Adds `coin` to the running total of its denom in `totals`
+pub fn add_total(
+    store: &mut dyn Storage,
+    totals: &Map<&str, Uint128>,
+    coin: &Coin,
+) -> StdResult<()> {
+    let total = totals.may_load(store, &coin.denom)?.unwrap_or_default();
+    totals.save(store, &coin.denom, &total.checked_add(coin.amount)?)
+}
+
+/// Subtracts `coin` from the running total of its denom in `totals`
+pub fn sub_total(
+    store: &mut dyn Storage,
+    totals: &Map<&str, Uint128>,
+    coin: &Coin,
+) -> StdResult<()> {
+    let total = totals.may_load(store, &coin.denom)?.unwrap_or_default();
+    let total = total.checked_sub(coin.amount)?;
+    if total.is_zero() {
+        totals.remove(store, &coin.denom);
+        Ok(())
+    } else {
+        totals.save(store, &coin.denom, &total)
+    }
+}
 
 pub fn consume_next_id(store: &mut dyn Storage) -> StdResult<u64> {
     let id: u64 = PROPOSAL_COUNT.may_load(store)?.unwrap_or_default() + 1;
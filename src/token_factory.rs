@@ -0,0 +1,65 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{CosmosMsg, Uint128};
+
+/// The custom messages of Juno's token factory module, with the same JSON as the
+/// `TokenFactoryMsg` of the `token-bindings` crate. That crate needs cosmwasm-std 1.0,
+/// so the messages proposals can use are mirrored here instead.
+/// Enabled with the `token-factory` feature, so proposals can create and manage native denoms
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenFactoryMsg {
+    Token(TokenMsg),
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenMsg {
+    /// Creates the denom `factory/{multisig address}/{subdenom}`, with the multisig as its admin
+    CreateDenom {
+        subdenom: String,
+        metadata: Option<Metadata>,
+    },
+    /// Transfers the admin rights of a denom
+    ChangeAdmin {
+        denom: String,
+        new_admin_address: String,
+    },
+    /// Mints tokens of a denom the multisig is the admin of
+    MintTokens {
+        denom: String,
+        amount: Uint128,
+        mint_to_address: String,
+    },
+    /// Burns tokens of a denom the multisig is the admin of
+    BurnTokens {
+        denom: String,
+        amount: Uint128,
+        burn_from_address: String,
+    },
+}
+
+/// The bank metadata of a denom
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct Metadata {
+    pub description: Option<String>,
+    pub denom_units: Vec<DenomUnit>,
+    pub base: Option<String>,
+    pub display: Option<String>,
+    pub name: Option<String>,
+    pub symbol: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct DenomUnit {
+    pub denom: String,
+    pub exponent: u32,
+    pub aliases: Vec<String>,
+}
+
+impl From<TokenMsg> for CosmosMsg<TokenFactoryMsg> {
+    fn from(msg: TokenMsg) -> Self {
+        CosmosMsg::Custom(TokenFactoryMsg::Token(msg))
+    }
+}